@@ -0,0 +1,74 @@
+//! Compares the time spent intersecting rays with the `complex` scene when its
+//! surfaces are stored in a plain `SurfList` and in a `Bvh`.
+//!
+//! Run with `cargo bench --bench bvh`.
+
+use radiant::{
+    bvh::Bvh,
+    misc::{self, Rng},
+    scene::{self, SceneType},
+    settings::RenderSettings,
+    surf::{HitRecord, Surface},
+};
+use std::time::{Duration, Instant};
+
+/// Trace the primary rays of a small image, returning the elapsed time and the
+/// number of rays that hit the world.
+fn trace(world: &dyn Surface, settings: &RenderSettings) -> (Duration, usize) {
+    let camera = complex_scene().camera.build(settings);
+    let mut rng = Rng::new(0);
+    let mut hits = 0;
+    let start = Instant::now();
+    for y in 0..settings.image_height {
+        for x in 0..settings.image_width {
+            for _ in 0..settings.samples_per_pixel {
+                let u = (x as f32 + rng.rand()) / (settings.image_width - 1) as f32;
+                let v = (y as f32 + rng.rand()) / (settings.image_height - 1) as f32;
+                let mut rec = HitRecord::new();
+                if world.hit(
+                    &camera.get_ray(u, v, (rng.rand(), rng.rand())),
+                    0.001,
+                    misc::INFTY,
+                    &mut rec,
+                ) {
+                    hits += 1;
+                }
+            }
+        }
+    }
+    (start.elapsed(), hits)
+}
+
+fn complex_scene() -> scene::Scene {
+    scene::make_scene(SceneType::Complex, &mut Rng::new(0))
+}
+
+fn main() {
+    let settings = RenderSettings {
+        image_width: 256,
+        image_height: 144,
+        samples_per_pixel: 8,
+        ..RenderSettings::default()
+    };
+
+    let list = complex_scene().world;
+    let surfaces = list.len();
+    let (linear_time, linear_hits) = trace(&list, &settings);
+
+    let build_start = Instant::now();
+    let bvh = Bvh::new(complex_scene().world);
+    let build_time = build_start.elapsed();
+    let (bvh_time, bvh_hits) = trace(&bvh, &settings);
+
+    let rays = settings.image_width * settings.image_height * settings.samples_per_pixel as usize;
+    println!("complex scene: {} surfaces, {} rays", surfaces, rays);
+    println!("SurfList: {:>10.2?} ({} hits)", linear_time, linear_hits);
+    println!(
+        "Bvh:      {:>10.2?} ({} hits, built in {:.2?})",
+        bvh_time, bvh_hits, build_time
+    );
+    println!(
+        "speedup:  {:>10.1}x",
+        linear_time.as_secs_f64() / bvh_time.as_secs_f64()
+    );
+}
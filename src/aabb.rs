@@ -0,0 +1,87 @@
+use crate::{ray::Ray, vec3::Vec3};
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    min: Vec3<f32>,
+    max: Vec3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3<f32>, max: Vec3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Smallest box containing every one of the given points.
+    pub fn from_points(points: &[Vec3<f32>]) -> Self {
+        let mut bbox = Self::new(points[0], points[0]);
+        for p in points.iter().skip(1) {
+            bbox = bbox.surrounding(&Self::new(*p, *p));
+        }
+        bbox
+    }
+
+    pub fn min(&self) -> Vec3<f32> {
+        self.min
+    }
+
+    pub fn max(&self) -> Vec3<f32> {
+        self.max
+    }
+
+    /// Smallest box containing both `self` and `other`.
+    pub fn surrounding(&self, other: &Self) -> Self {
+        Self {
+            min: Vec3::new(
+                f32::min(self.min.x(), other.min.x()),
+                f32::min(self.min.y(), other.min.y()),
+                f32::min(self.min.z(), other.min.z()),
+            ),
+            max: Vec3::new(
+                f32::max(self.max.x(), other.max.x()),
+                f32::max(self.max.y(), other.max.y()),
+                f32::max(self.max.z(), other.max.z()),
+            ),
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    /// Index of the axis along which the box is the longest.
+    pub fn longest_axis(&self) -> u8 {
+        let d = self.max - self.min;
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Slab test of the ray against the box, where `inv_dir` holds the inverse
+    /// of each component of the ray direction.
+    pub fn hit(&self, ray: &Ray, inv_dir: &Vec3<f32>, mut t_min: f32, mut t_max: f32) -> bool {
+        let origin = ray.origin();
+        for axis in 0..3 {
+            let mut t0 = (self.min[axis] - origin[axis]) * inv_dir[axis];
+            let mut t1 = (self.max[axis] - origin[axis]) * inv_dir[axis];
+            if inv_dir[axis] < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
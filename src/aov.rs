@@ -0,0 +1,265 @@
+use crate::{color::Color, film::Film, tonemap::ToneMap, vec3::Vec3};
+use image::{ImageBuffer, Rgb, RgbImage};
+use std::{
+    fmt::{self, Display},
+    ops::{AddAssign, Mul},
+    str::FromStr,
+};
+
+/// Arbitrary output variable, an auxiliary image rendered along with the
+/// radiance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    /// Albedo of the material first hit by the camera rays.
+    Albedo,
+    /// Shading normal at the first hit, facing the camera.
+    Normal,
+    /// Ray parameter of the first hit, zero for the background.
+    Depth,
+    /// World position of the first hit, zero for the background.
+    Position,
+    /// Identifier of the object seen through the center of the pixel.
+    ObjectId,
+    /// Identifier of the material seen through the center of the pixel.
+    MaterialId,
+    /// Light emitted towards the camera, or reaching the first hit straight
+    /// from an emitter or the background.
+    Direct,
+    /// Light reaching the first hit after bouncing at least once more.
+    Indirect,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 8] = [
+        Self::Albedo,
+        Self::Normal,
+        Self::Depth,
+        Self::Position,
+        Self::ObjectId,
+        Self::MaterialId,
+        Self::Direct,
+        Self::Indirect,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Albedo => "albedo",
+            Self::Normal => "normal",
+            Self::Depth => "depth",
+            Self::Position => "position",
+            Self::ObjectId => "object_id",
+            Self::MaterialId => "material_id",
+            Self::Direct => "direct",
+            Self::Indirect => "indirect",
+        }
+    }
+
+    /// Names of the channels of the variable in OpenEXR files.
+    pub fn channel_names(self) -> &'static [&'static str] {
+        match self {
+            Self::Albedo | Self::Direct | Self::Indirect => &["R", "G", "B"],
+            Self::Normal | Self::Position => &["X", "Y", "Z"],
+            Self::Depth => &["Z"],
+            Self::ObjectId | Self::MaterialId => &["id"],
+        }
+    }
+
+    /// Whether the variable holds integer identifiers rather than averaged
+    /// float values.
+    pub fn is_id(self) -> bool {
+        matches!(self, Self::ObjectId | Self::MaterialId)
+    }
+
+    /// Float channels of the variable, each with its rows from the top of the
+    /// image to the bottom. Identifiers are given by `ids` instead.
+    pub fn channels(self, film: &Film) -> Vec<Vec<f32>> {
+        let mut channels = vec![Vec::new(); self.channel_names().len()];
+        for (x, y) in top_down(film) {
+            let values = film.aov(x, y).unwrap_or_default();
+            let components: [f32; 3] = match self {
+                Self::Albedo => values.albedo.channels(),
+                Self::Normal => vec_components(values.normal),
+                Self::Depth => [values.depth; 3],
+                Self::Position => vec_components(values.position),
+                Self::Direct => values.direct.channels(),
+                Self::Indirect => values.indirect.channels(),
+                Self::ObjectId | Self::MaterialId => [0.0; 3],
+            };
+            for (channel, value) in channels.iter_mut().zip(components) {
+                channel.push(value);
+            }
+        }
+        channels
+    }
+
+    /// Identifiers of the variable with the rows from the top of the image to
+    /// the bottom, or zeros for other variables.
+    pub fn ids(self, film: &Film) -> Vec<u32> {
+        top_down(film)
+            .map(|(x, y)| {
+                let (object, material) = film.ids(x, y).unwrap_or_default();
+                match self {
+                    Self::ObjectId => object,
+                    Self::MaterialId => material,
+                    _ => 0,
+                }
+            })
+            .collect()
+    }
+
+    /// Linear RGB pixels for float formats without named channels, with the
+    /// rows from the top of the image to the bottom. Depths are repeated over
+    /// the three channels, and identifiers are shown as random colors.
+    pub fn rgb_pixels(self, film: &Film) -> Vec<[f32; 3]> {
+        if self.is_id() {
+            return self.ids(film).into_iter().map(id_color).collect();
+        }
+        let channels = self.channels(film);
+        (0..film.width() * film.height())
+            .map(|i| match channels.as_slice() {
+                [value] => [value[i]; 3],
+                [r, g, b] => [r[i], g[i], b[i]],
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    /// 8-bit preview of the variable. Lighting goes through `tone_map` like the
+    /// radiance, while the other variables are rescaled into the displayable
+    /// range.
+    pub fn to_rgb_image(self, film: &Film, tone_map: &ToneMap) -> RgbImage {
+        let pixels = self.rgb_pixels(film);
+        let (low, high) = match self {
+            // Normals span [-1, 1]
+            Self::Normal => ([-1.0; 3], [1.0; 3]),
+            Self::Depth | Self::Position => bounds(&pixels),
+            _ => ([0.0; 3], [1.0; 3]),
+        };
+        let max_luminance = pixels
+            .iter()
+            .map(|&[r, g, b]| Color::new(r, g, b).luminance())
+            .fold(0.0, f32::max);
+        ImageBuffer::from_fn(film.width() as u32, film.height() as u32, |x, y| {
+            let [r, g, b] = pixels[(y * film.width() as u32 + x) as usize];
+            let color = Color::new(r, g, b);
+            match self {
+                Self::Direct | Self::Indirect => tone_map.apply(color, max_luminance).rgb(),
+                Self::Albedo | Self::ObjectId | Self::MaterialId => color.rgb(),
+                // Geometric variables are shown without the sRGB encoding
+                Self::Normal | Self::Depth | Self::Position => {
+                    let mut scaled = [0; 3];
+                    for (i, value) in scaled.iter_mut().enumerate() {
+                        let range = (high[i] - low[i]).max(f32::EPSILON);
+                        let t = ((color.channels()[i] - low[i]) / range).clamp(0.0, 1.0);
+                        *value = (255.0 * t).round() as u8;
+                    }
+                    Rgb(scaled)
+                }
+            }
+        })
+    }
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_ascii_lowercase().replace('-', "_");
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|aov| aov.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|aov| aov.name()).collect();
+                format!("unknown AOV `{}`, expected one of {}", s, names.join(", "))
+            })
+    }
+}
+
+impl Display for Aov {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Values of the averaged output variables for a single sample, or their
+/// weighted sum over the samples of a pixel.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AovValues {
+    pub albedo: Color,
+    pub normal: Vec3<f32>,
+    pub depth: f32,
+    pub position: Vec3<f32>,
+    pub direct: Color,
+    pub indirect: Color,
+    /// Light emitted towards the camera by the first hit, or the background
+    /// seen directly, which the denoiser leaves out of its filter.
+    pub emission: Color,
+}
+
+impl AddAssign for AovValues {
+    fn add_assign(&mut self, other: Self) {
+        self.albedo += other.albedo;
+        self.normal += other.normal;
+        self.depth += other.depth;
+        self.position += other.position;
+        self.direct += other.direct;
+        self.indirect += other.indirect;
+        self.emission += other.emission;
+    }
+}
+
+impl Mul<f32> for AovValues {
+    type Output = Self;
+
+    fn mul(self, t: f32) -> Self {
+        Self {
+            albedo: self.albedo * t,
+            normal: self.normal * t,
+            depth: self.depth * t,
+            position: self.position * t,
+            direct: self.direct * t,
+            indirect: self.indirect * t,
+            emission: self.emission * t,
+        }
+    }
+}
+
+/// Pixel coordinates of the film, with the rows from the top of the image to
+/// the bottom.
+fn top_down(film: &Film) -> impl Iterator<Item = (usize, usize)> + '_ {
+    (0..film.height())
+        .rev()
+        .flat_map(move |y| (0..film.width()).map(move |x| (x, y)))
+}
+
+fn vec_components(v: Vec3<f32>) -> [f32; 3] {
+    [v.x(), v.y(), v.z()]
+}
+
+/// Smallest and largest value of every channel.
+fn bounds(pixels: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    let mut low = [f32::INFINITY; 3];
+    let mut high = [f32::NEG_INFINITY; 3];
+    for pixel in pixels {
+        for i in 0..3 {
+            low[i] = low[i].min(pixel[i]);
+            high[i] = high[i].max(pixel[i]);
+        }
+    }
+    (low, high)
+}
+
+/// Bright random color standing for an identifier, black for zero.
+fn id_color(id: u32) -> [f32; 3] {
+    if id == 0 {
+        return [0.0; 3];
+    }
+    let mut hash = id.wrapping_mul(0x9e3779b9);
+    [0, 1, 2].map(|_| {
+        hash ^= hash >> 16;
+        hash = hash.wrapping_mul(0x85ebca6b);
+        hash ^= hash >> 13;
+        0.2 + 0.8 * (hash & 0xff) as f32 / 255.0
+    })
+}
@@ -0,0 +1,162 @@
+use crate::{
+    color::{self, Color},
+    misc,
+    vec3::Vec3,
+};
+use image::{codecs::hdr::HdrDecoder, DynamicImage, ImageBuffer, ImageFormat, ImageResult};
+use std::{fs::File, io::BufReader, path::Path};
+
+/// Light reaching the rays that escape the world.
+pub enum Background {
+    /// The same color in every direction.
+    Solid(Color),
+    /// Vertical blend from the `bottom` color straight down to the `top` color
+    /// straight up.
+    Gradient {
+        bottom: Color,
+        top: Color,
+    },
+    EnvMap(EnvironmentMap),
+}
+
+impl Background {
+    /// Radiance arriving from the given direction, which need not be normalized.
+    pub fn radiance(&self, direction: Vec3<f32>) -> Color {
+        match self {
+            Self::Solid(color) => *color,
+            Self::Gradient { bottom, top } => {
+                let t = 0.5 * (direction.unit_vector().y() + 1.0);
+                *bottom * (1.0 - t) + *top * t
+            }
+            Self::EnvMap(map) => map.radiance(direction),
+        }
+    }
+}
+
+impl Default for Background {
+    // White to light blue sky
+    fn default() -> Self {
+        Self::Gradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+/// Equirectangular environment map surrounding the whole scene.
+///
+/// The top row of the image is straight up, and the center of the image looks
+/// down the negative z axis before the map is rotated.
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,
+    rotation: f32,
+    intensity: f32,
+}
+
+impl EnvironmentMap {
+    /// Load the map from any image format supported by the `image` crate.
+    /// Floating point images such as Radiance `.hdr` and OpenEXR files are
+    /// taken to hold linear radiance, while 8 and 16 bit images are decoded
+    /// from sRGB.
+    pub fn load(path: &Path) -> ImageResult<Self> {
+        let image = if ImageFormat::from_path(path)? == ImageFormat::Hdr {
+            // Going through `image::open` would clamp Radiance images to 8 bits
+            let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder.read_image_hdr()?;
+            let buffer = ImageBuffer::from_fn(metadata.width, metadata.height, |x, y| {
+                pixels[(y * metadata.width + x) as usize]
+            });
+            DynamicImage::ImageRgb32F(buffer)
+        } else {
+            image::open(path)?
+        };
+        let linear = matches!(
+            image,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+        );
+        let image = image.into_rgb32f();
+        let texels = image
+            .pixels()
+            .map(|p| {
+                let [r, g, b] = if linear {
+                    p.0
+                } else {
+                    p.0.map(color::srgb_to_linear)
+                };
+                Color::new(r, g, b)
+            })
+            .collect();
+        Ok(Self {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            texels,
+            rotation: 0.0,
+            intensity: 1.0,
+        })
+    }
+
+    /// Rotate the map by `degrees` counter-clockwise about the vertical axis.
+    pub fn with_rotation(mut self, degrees: f32) -> Self {
+        self.rotation = degrees.to_radians();
+        self
+    }
+
+    /// Scale the radiance of the map by `intensity`.
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    fn radiance(&self, direction: Vec3<f32>) -> Color {
+        let d = direction.unit_vector();
+        let phi = f32::atan2(d.x(), -d.z()) + self.rotation;
+        let theta = f32::acos(d.y().clamp(-1.0, 1.0));
+        let u = (phi / (2.0 * misc::PI) + 0.5).rem_euclid(1.0);
+        let v = theta / misc::PI;
+        self.lookup(u, v) * self.intensity
+    }
+
+    /// Bilinear lookup, wrapping around horizontally.
+    fn lookup(&self, u: f32, v: f32) -> Color {
+        let x = u * self.width as f32 - 0.5;
+        let y = (v * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        let x0 = (x0 as isize).rem_euclid(self.width as isize) as usize;
+        let x1 = (x0 + 1) % self.width;
+        let y0 = y0 as usize;
+        let y1 = (y0 + 1).min(self.height - 1);
+
+        let texel = |x: usize, y: usize| self.texels[y * self.width + x];
+        let top = texel(x0, y0) * (1.0 - fx) + texel(x1, y0) * fx;
+        let bottom = texel(x0, y1) * (1.0 - fx) + texel(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{codecs::hdr::HdrEncoder, Rgb};
+
+    #[test]
+    fn radiance_images_keep_values_above_one() {
+        let path = std::env::temp_dir().join(format!("radiant-env-{}.hdr", std::process::id()));
+        let pixels = [Rgb([4.0, 2.0, 0.5]), Rgb([16.0, 1.0, 0.25])];
+        HdrEncoder::new(File::create(&path).unwrap())
+            .encode(&pixels, 2, 1)
+            .unwrap();
+        let map = EnvironmentMap::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let map = map.unwrap();
+        assert_eq!((map.width, map.height), (2, 1));
+        for (texel, pixel) in map.texels.iter().zip(&pixels) {
+            assert_eq!(texel.channels(), pixel.0);
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use crate::{
+    aabb::Aabb,
+    ray::Ray,
+    surf::{HitRecord, Surface},
+    surf_list::SurfList,
+    vec3::Vec3,
+};
+
+/// Number of buckets in which the centroids are binned when evaluating the
+/// surface area heuristic.
+const SAH_BUCKETS: usize = 12;
+
+/// Cost of traversing an interior node, relative to the cost of intersecting a
+/// surface.
+const TRAVERSAL_COST: f32 = 0.125;
+
+/// Maximum number of surfaces a leaf may hold when a split would not pay off.
+const MAX_LEAF_SURFACES: usize = 4;
+
+/// Size of the stack of nodes left to visit when tracing a ray. Traversal
+/// holds at most one node per level plus one, so deeper nodes are not split.
+const STACK_SIZE: usize = 64;
+
+enum BvhNode {
+    /// Leaf holding the surfaces `first..first + count`.
+    Leaf {
+        bbox: Aabb,
+        first: usize,
+        count: usize,
+    },
+    /// Interior node whose left child is the next node in the array and whose
+    /// right child is at index `right`.
+    Interior { bbox: Aabb, right: usize, axis: u8 },
+}
+
+impl BvhNode {
+    fn bbox(&self) -> &Aabb {
+        match self {
+            Self::Leaf { bbox, .. } | Self::Interior { bbox, .. } => bbox,
+        }
+    }
+}
+
+struct SurfaceInfo {
+    index: usize,
+    bbox: Aabb,
+    centroid: Vec3<f32>,
+}
+
+/// Bounding volume hierarchy built with the surface area heuristic.
+///
+/// The nodes are flattened in depth-first order. Surfaces without a bounding
+/// box cannot be placed in the hierarchy and are tested one by one instead.
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    /// Surfaces paired with the identifier of their object in the list the
+    /// hierarchy was built from.
+    surfaces: Vec<(u32, Box<dyn Surface>)>,
+    unbounded: Vec<(u32, Box<dyn Surface>)>,
+}
+
+impl Bvh {
+    pub fn new(list: SurfList) -> Self {
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        for (object_id, surf) in list {
+            match surf.bounding_box() {
+                Some(_) => bounded.push((object_id, surf)),
+                None => unbounded.push((object_id, surf)),
+            }
+        }
+
+        let mut infos: Vec<SurfaceInfo> = bounded
+            .iter()
+            .enumerate()
+            .map(|(index, (_, surf))| {
+                let bbox = surf.bounding_box().unwrap();
+                SurfaceInfo {
+                    index,
+                    bbox,
+                    centroid: bbox.centroid(),
+                }
+            })
+            .collect();
+
+        let mut nodes = Vec::new();
+        if !infos.is_empty() {
+            Self::build(&mut nodes, &mut infos, 0, 0);
+        }
+
+        // Reorder the surfaces so that every leaf refers to a contiguous range
+        let mut slots: Vec<Option<(u32, Box<dyn Surface>)>> =
+            bounded.into_iter().map(Some).collect();
+        let surfaces = infos
+            .iter()
+            .map(|info| slots[info.index].take().unwrap())
+            .collect();
+
+        Self {
+            nodes,
+            surfaces,
+            unbounded,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.surfaces.len() + self.unbounded.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Recursively build the subtree for `infos`, whose first surface is at
+    /// position `offset` of the final surface array and whose root is at the
+    /// given `depth`. Returns the index of the root node of the subtree.
+    fn build(
+        nodes: &mut Vec<BvhNode>,
+        infos: &mut [SurfaceInfo],
+        offset: usize,
+        depth: usize,
+    ) -> usize {
+        let bbox = infos
+            .iter()
+            .skip(1)
+            .fold(infos[0].bbox, |b, info| b.surrounding(&info.bbox));
+        let node_index = nodes.len();
+        let leaf = BvhNode::Leaf {
+            bbox,
+            first: offset,
+            count: infos.len(),
+        };
+
+        if infos.len() == 1 || depth + 1 >= STACK_SIZE {
+            nodes.push(leaf);
+            return node_index;
+        }
+
+        let centroid_bounds = infos.iter().skip(1).fold(
+            Aabb::new(infos[0].centroid, infos[0].centroid),
+            |b, info| b.surrounding(&Aabb::new(info.centroid, info.centroid)),
+        );
+        let axis = centroid_bounds.longest_axis();
+        let c_min = centroid_bounds.min()[axis];
+        let c_max = centroid_bounds.max()[axis];
+
+        let mid = if c_max - c_min <= f32::EPSILON {
+            // Every centroid coincides, so splitting cannot separate them
+            if infos.len() <= MAX_LEAF_SURFACES {
+                nodes.push(leaf);
+                return node_index;
+            }
+            infos.len() / 2
+        } else {
+            let bucket_of = |info: &SurfaceInfo| {
+                let b =
+                    ((info.centroid[axis] - c_min) / (c_max - c_min) * SAH_BUCKETS as f32) as usize;
+                b.min(SAH_BUCKETS - 1)
+            };
+
+            let mut counts = [0usize; SAH_BUCKETS];
+            let mut bounds: [Option<Aabb>; SAH_BUCKETS] = [None; SAH_BUCKETS];
+            for info in infos.iter() {
+                let b = bucket_of(info);
+                counts[b] += 1;
+                bounds[b] = Some(match bounds[b] {
+                    Some(bb) => bb.surrounding(&info.bbox),
+                    None => info.bbox,
+                });
+            }
+
+            // Cost of splitting after each bucket
+            let mut best_cost = f32::INFINITY;
+            let mut best_split = 0;
+            for split in 0..SAH_BUCKETS - 1 {
+                let (left_count, left_bbox) = Self::merge_buckets(&counts, &bounds, 0..=split);
+                let (right_count, right_bbox) =
+                    Self::merge_buckets(&counts, &bounds, split + 1..=SAH_BUCKETS - 1);
+                if left_count == 0 || right_count == 0 {
+                    continue;
+                }
+                let cost = TRAVERSAL_COST
+                    + (left_count as f32 * left_bbox.unwrap().surface_area()
+                        + right_count as f32 * right_bbox.unwrap().surface_area())
+                        / bbox.surface_area();
+                if cost < best_cost {
+                    best_cost = cost;
+                    best_split = split;
+                }
+            }
+
+            if infos.len() <= MAX_LEAF_SURFACES && best_cost >= infos.len() as f32 {
+                nodes.push(leaf);
+                return node_index;
+            }
+            Self::partition(infos, |info| bucket_of(info) <= best_split)
+        };
+
+        // Placeholder, replaced once the right child index is known
+        nodes.push(leaf);
+        let (left, right) = infos.split_at_mut(mid);
+        Self::build(nodes, left, offset, depth + 1);
+        let right_index = Self::build(nodes, right, offset + mid, depth + 1);
+        nodes[node_index] = BvhNode::Interior {
+            bbox,
+            right: right_index,
+            axis,
+        };
+        node_index
+    }
+
+    fn merge_buckets(
+        counts: &[usize; SAH_BUCKETS],
+        bounds: &[Option<Aabb>; SAH_BUCKETS],
+        range: std::ops::RangeInclusive<usize>,
+    ) -> (usize, Option<Aabb>) {
+        let mut count = 0;
+        let mut bbox: Option<Aabb> = None;
+        for b in range {
+            count += counts[b];
+            if let Some(bb) = bounds[b] {
+                bbox = Some(match bbox {
+                    Some(acc) => acc.surrounding(&bb),
+                    None => bb,
+                });
+            }
+        }
+        (count, bbox)
+    }
+
+    /// Move the elements satisfying `pred` to the front of the slice and return
+    /// how many there are.
+    fn partition<F: Fn(&SurfaceInfo) -> bool>(infos: &mut [SurfaceInfo], pred: F) -> usize {
+        let mut first_false = 0;
+        for i in 0..infos.len() {
+            if pred(&infos[i]) {
+                infos.swap(i, first_false);
+                first_false += 1;
+            }
+        }
+        first_false
+    }
+}
+
+impl Surface for Bvh {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+
+        for (object_id, surf) in self.unbounded.iter() {
+            if surf.hit(ray, t_min, closest_so_far, rec) {
+                hit_anything = true;
+                closest_so_far = rec.parameter();
+                rec.set_object_id(*object_id);
+            }
+        }
+
+        if self.nodes.is_empty() {
+            return hit_anything;
+        }
+
+        let dir = ray.direction();
+        let inv_dir = Vec3::new(1.0 / dir.x(), 1.0 / dir.y(), 1.0 / dir.z());
+        let mut stack = [0; STACK_SIZE];
+        let mut stack_len = 1;
+        while stack_len > 0 {
+            stack_len -= 1;
+            let node_index = stack[stack_len];
+            let node = &self.nodes[node_index];
+            if !node.bbox().hit(ray, &inv_dir, t_min, closest_so_far) {
+                continue;
+            }
+            match *node {
+                BvhNode::Leaf { first, count, .. } => {
+                    for (object_id, surf) in self.surfaces[first..first + count].iter() {
+                        if surf.hit(ray, t_min, closest_so_far, rec) {
+                            hit_anything = true;
+                            closest_so_far = rec.parameter();
+                            rec.set_object_id(*object_id);
+                        }
+                    }
+                }
+                BvhNode::Interior { right, axis, .. } => {
+                    // Visit the nearest child first, so that farther surfaces
+                    // can be culled by the closest hit found so far
+                    let (near, far) = if inv_dir[axis] < 0.0 {
+                        (right, node_index + 1)
+                    } else {
+                        (node_index + 1, right)
+                    };
+                    stack[stack_len] = far;
+                    stack[stack_len + 1] = near;
+                    stack_len += 2;
+                }
+            }
+        }
+        hit_anything
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.nodes.first().map(|node| *node.bbox())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        misc::Rng,
+        scene::{self, SceneType},
+    };
+
+    #[test]
+    fn finds_the_same_closest_hit_as_the_list() {
+        let scene = || scene::make_scene(SceneType::Complex, &mut Rng::new(3));
+        let list = scene().world;
+        let bvh = Bvh::new(scene().world);
+        let camera = scene().camera;
+
+        // Rays from the camera through a grid covering the scene
+        let (columns, rows) = (64, 32);
+        // Objects hit, to make sure the rays cover the scene
+        let mut object_ids = std::collections::HashSet::new();
+        for i in 0..columns * rows {
+            let target = camera.lookat
+                + Vec3::new(
+                    0.0,
+                    4.0 * (i / columns) as f32 / rows as f32 - 1.0,
+                    16.0 * (i % columns) as f32 / columns as f32 - 8.0,
+                );
+            let ray = Ray::new(camera.lookfrom, target - camera.lookfrom);
+            let mut list_rec = HitRecord::new();
+            let mut bvh_rec = HitRecord::new();
+            let list_hit = list.hit(&ray, 0.001, f32::INFINITY, &mut list_rec);
+            let bvh_hit = bvh.hit(&ray, 0.001, f32::INFINITY, &mut bvh_rec);
+            assert_eq!(list_hit, bvh_hit, "ray {}", i);
+            if list_hit {
+                assert_eq!(list_rec.parameter(), bvh_rec.parameter(), "ray {}", i);
+                assert_eq!(list_rec.object_id(), bvh_rec.object_id(), "ray {}", i);
+                object_ids.insert(list_rec.object_id());
+            }
+        }
+        assert!(
+            object_ids.len() > 50,
+            "only {} objects hit",
+            object_ids.len()
+        );
+    }
+}
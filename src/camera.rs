@@ -1,20 +1,86 @@
-use crate::{misc, ray::Ray, vec3::Vec3};
+use crate::{misc, ray::Ray, sampler, settings::RenderSettings, vec3::Vec3};
+use std::str::FromStr;
 
-/// Aspect ratio of the displayed image .
-pub const ASPECT_RATIO: f32 = 16.0 / 9.0;
+/// Distance from the camera to the plane in perfect focus.
+#[derive(Debug, Clone, Copy)]
+pub enum FocusDistance {
+    /// Focus on the point the camera looks at, that is, at the distance
+    /// `|lookfrom - lookat|`.
+    Auto,
+    Fixed(f32),
+}
 
-/// Width of the displayed image.
-pub const IMAGE_WIDTH: usize = 1024;
+impl FromStr for FocusDistance {
+    type Err = String;
 
-/// Height of the displayed image. It is given by the image width divided by the
-/// aspect ratio.
-pub const IMAGE_HEIGHT: usize = 576; // (IMAGE_WIDTH * 9.0 / 16.0) as i32;
+    /// Parse either `auto` or a positive number.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(Self::Auto);
+        }
+        match s.parse::<f32>() {
+            Ok(d) if d > 0.0 => Ok(Self::Fixed(d)),
+            _ => Err(format!(
+                "expected `auto` or a positive number, found `{}`",
+                s
+            )),
+        }
+    }
+}
 
-/// Number of samples used per pixel in the process of aliasing.
-pub const SAMPLES_PER_PIXEL: i32 = 20;
+/// Parameters describing how a `Camera` is positioned in the scene.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraParams {
+    /// The point the camera looks from.
+    pub lookfrom: Vec3<f32>,
+    /// The point the camera looks at.
+    pub lookat: Vec3<f32>,
+    /// Vector up.
+    pub vup: Vec3<f32>,
+    /// Vertical field-of-view in degrees.
+    pub vfov: f32,
+    /// Aperture of the camera.
+    pub aperture: f32,
+    /// Distance of focus.
+    pub focus_dist: FocusDistance,
+}
+
+impl CameraParams {
+    /// Distance of focus, resolving `FocusDistance::Auto` to the distance
+    /// between `lookfrom` and `lookat`.
+    pub fn focus_distance(&self) -> f32 {
+        match self.focus_dist {
+            FocusDistance::Auto => (self.lookfrom - self.lookat).len(),
+            FocusDistance::Fixed(d) => d,
+        }
+    }
 
-/// Maximum number of bounces of a ray.
-pub const MAX_DEPTH: i32 = 8;
+    /// Build a `Camera` whose aspect ratio is given by the render `settings`.
+    pub fn build(&self, settings: &RenderSettings) -> Camera {
+        Camera::new(
+            self.lookfrom,
+            self.lookat,
+            self.vup,
+            self.vfov,
+            self.aperture,
+            self.focus_distance(),
+            settings,
+        )
+    }
+}
+
+impl Default for CameraParams {
+    fn default() -> Self {
+        Self {
+            lookfrom: Vec3::new(13.0, 2.0, 3.0),
+            lookat: Vec3::new(0.0, 0.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 20.0,
+            aperture: 0.1,
+            focus_dist: FocusDistance::Auto,
+        }
+    }
+}
 
 pub struct Camera {
     pub aspect_ratio: f32,
@@ -38,19 +104,20 @@ impl Camera {
     /// * `lookat` - The point the camera looks at.
     /// * `vup` - Vector up.
     /// * `vfov` - Vertical field-of-view in degrees.
-    /// * `aspect_ratio` - Aspect ratio of the camera.
     /// * `aperture` - Aperture of the camera.
     /// * `focus_dist` - Distance of focus.
+    /// * `settings` - Render settings, from which the aspect ratio is derived.
     pub fn new(
         lookfrom: Vec3<f32>,
         lookat: Vec3<f32>,
         vup: Vec3<f32>,
         vfov: f32,
-        aspect_ratio: f32,
         aperture: f32,
         focus_dist: f32,
+        settings: &RenderSettings,
     ) -> Self {
         // Viewport
+        let aspect_ratio = settings.aspect_ratio();
         let theta = misc::deg_to_rad(vfov);
         let h = f32::tan(theta / 2.0);
         let viewport_height = 2.0 * h;
@@ -86,8 +153,10 @@ impl Camera {
         }
     }
 
-    pub fn get_ray(&self, u: f32, v: f32) -> Ray {
-        let rd = Vec3::random_in_unit_disk(&mut rand::thread_rng()) * self.lens_radius;
+    /// Ray through the point `(u, v)` of the viewport, leaving the point of the
+    /// lens picked by the sample values `lens`, uniformly distributed in [0, 1).
+    pub fn get_ray(&self, u: f32, v: f32, lens: (f32, f32)) -> Ray {
+        let rd = sampler::concentric_disk(lens) * self.lens_radius;
         let offset = self.ortho_basis.1 * rd.x() + self.ortho_basis.2 * rd.y();
         Ray::new(
             self.origin + offset,
@@ -1,6 +1,5 @@
-use crate::camera;
+use crate::misc::Rng;
 use image::Rgb;
-use rand::{rngs::ThreadRng, Rng};
 use std::{
     fmt::{self, Display},
     ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign},
@@ -18,31 +17,70 @@ impl Color {
         Self { r, g, b }
     }
 
-    pub fn rand(rng: &mut ThreadRng) -> Self {
+    pub fn rand(rng: &mut Rng) -> Self {
         Self {
-            r: rng.gen(),
-            g: rng.gen(),
-            b: rng.gen(),
+            r: rng.rand(),
+            g: rng.rand(),
+            b: rng.rand(),
         }
     }
 
-    pub fn rand_on(rng: &mut ThreadRng, min: f32, max: f32) -> Self {
+    pub fn rand_on(rng: &mut Rng, min: f32, max: f32) -> Self {
         Self {
-            r: rng.gen_range(min..max),
-            g: rng.gen_range(min..max),
-            b: rng.gen_range(min..max),
+            r: rng.rand_on(min, max),
+            g: rng.rand_on(min, max),
+            b: rng.rand_on(min, max),
         }
     }
 
+    /// Largest of the three channels.
+    pub fn max_component(&self) -> f32 {
+        self.r.max(self.g).max(self.b)
+    }
+
+    /// The red, green and blue channels, in this order.
+    pub fn channels(self) -> [f32; 3] {
+        [self.r, self.g, self.b]
+    }
+
+    /// Relative luminance, using the Rec. 709 weights of the channels.
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    /// Apply `f` to every channel.
+    pub fn map(self, f: impl Fn(f32) -> f32) -> Self {
+        Self {
+            r: f(self.r),
+            g: f(self.g),
+            b: f(self.b),
+        }
+    }
+
+    /// Encode a linear color into an 8-bit sRGB color, clipping the channels
+    /// to `[0, 1]`. Radiance should be tone mapped first.
     pub fn rgb(self) -> Rgb<u8> {
-        // Gamma correction for gamma = 2.0
-        let scale = 1.0 / camera::SAMPLES_PER_PIXEL as f32;
-        let r = (f32::sqrt(self.r * scale)).clamp(0.0, 0.999);
-        let g = (f32::sqrt(self.g * scale)).clamp(0.0, 0.999);
-        let b = (f32::sqrt(self.b * scale)).clamp(0.0, 0.999);
-
-        // Translated colors to the interval [0, 255]
-        Rgb::from([(256.0 * r) as u8, (256.0 * g) as u8, (256.0 * b) as u8])
+        Rgb(self
+            .channels()
+            .map(|c| (255.0 * linear_to_srgb(c.clamp(0.0, 1.0))).round() as u8))
+    }
+}
+
+/// The sRGB transfer function, from linear values to encoded ones.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of the sRGB transfer function, from encoded values to linear ones.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
     }
 }
 
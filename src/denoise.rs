@@ -0,0 +1,193 @@
+use crate::{aov::AovValues, color::Color, film::Film, vec3::Vec3};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+/// Weights of the B3 spline kernel of the À-Trous transform, along one axis.
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Albedo below which a channel is not divided out of the radiance, as the
+/// division would only amplify noise.
+const MIN_ALBEDO: f32 = 0.01;
+
+/// Edge-avoiding À-Trous wavelet filter, after Dammertz et al., "Edge-Avoiding
+/// À-Trous Wavelet Transform for fast Global Illumination Filtering".
+///
+/// Every iteration blurs the image with a 5×5 kernel whose taps are spread
+/// twice as far apart as in the previous one. The weight of each tap falls
+/// with the difference in albedo and normal between the two pixels, so that
+/// the blur stops at the edges of objects and materials, and with their
+/// difference in luminance relative to its noise, so that it keeps the edges
+/// of shadows and highlights. As in spatiotemporal variance-guided filtering,
+/// the variance of the luminance is filtered along with the radiance.
+///
+/// The albedo is divided out of the radiance beforehand and multiplied back
+/// afterwards, which keeps the texture detail out of the filter.
+#[derive(Debug, Clone, Copy)]
+pub struct Denoiser {
+    /// Number of iterations; the kernel covers `4 · 2^iterations` pixels.
+    pub iterations: u32,
+    /// Differences in luminance that stop the blur, in standard deviations
+    /// of the noise.
+    pub sigma_luminance: f32,
+    /// Scale of the differences in normal that stop the blur.
+    pub sigma_normal: f32,
+    /// Scale of the differences in albedo that stop the blur.
+    pub sigma_albedo: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_luminance: 4.0,
+            sigma_normal: 0.3,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+impl Denoiser {
+    /// Denoise the `width` by `height` image `color`, guided by the variance
+    /// of the luminance of every pixel, and by the albedo and normal of its
+    /// first hit. The buffers hold the pixels row by row.
+    pub fn denoise(
+        &self,
+        (width, height): (usize, usize),
+        color: &[Color],
+        variance: &[f32],
+        albedo: &[Color],
+        normal: &[Vec3<f32>],
+    ) -> Vec<Color> {
+        let len = width * height;
+        assert!(
+            color.len() == len
+                && variance.len() == len
+                && albedo.len() == len
+                && normal.len() == len
+        );
+
+        let divisor: Vec<Color> = albedo
+            .iter()
+            .map(|a| a.map(|c| if c > MIN_ALBEDO { c } else { 1.0 }))
+            .collect();
+        let mut irradiance: Vec<[f32; 3]> = color
+            .iter()
+            .zip(&divisor)
+            .map(|(c, d)| {
+                let [r, g, b] = c.channels();
+                let [dr, dg, db] = d.channels();
+                [r / dr, g / dg, b / db]
+            })
+            .collect();
+        let mut variance: Vec<f32> = variance
+            .iter()
+            .zip(&divisor)
+            .map(|(v, d)| v / d.luminance().max(MIN_ALBEDO).powi(2))
+            .collect();
+        let albedo: Vec<[f32; 3]> = albedo.iter().map(|a| a.channels()).collect();
+        let normal: Vec<[f32; 3]> = normal.iter().map(|n| [n.x(), n.y(), n.z()]).collect();
+
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+            let (input, input_variance) = (&irradiance, &variance);
+            let filtered: Vec<([f32; 3], f32)> = (0..len)
+                .into_par_iter()
+                .map(|i| {
+                    let (x, y) = ((i % width) as isize, (i / width) as isize);
+                    let center = luminance(&input[i]);
+                    let mut sum = [0.0; 3];
+                    let mut variance_sum = 0.0;
+                    let mut weight_sum = 0.0;
+                    for (dy, ky) in KERNEL.iter().enumerate() {
+                        for (dx, kx) in KERNEL.iter().enumerate() {
+                            let qx = x + (dx as isize - 2) * step;
+                            let qy = y + (dy as isize - 2) * step;
+                            if qx < 0 || qy < 0 || qx >= width as isize || qy >= height as isize {
+                                continue;
+                            }
+                            let j = qx as usize + qy as usize * width;
+                            let noise = input_variance[i] + input_variance[j];
+                            let difference = center - luminance(&input[j]);
+                            let weight = kx
+                                * ky
+                                * f32::exp(
+                                    -difference * difference
+                                        / (self.sigma_luminance.powi(2) * noise + f32::EPSILON),
+                                )
+                                * edge_weight(&normal[i], &normal[j], self.sigma_normal)
+                                * edge_weight(&albedo[i], &albedo[j], self.sigma_albedo);
+                            for (s, v) in sum.iter_mut().zip(input[j]) {
+                                *s += weight * v;
+                            }
+                            variance_sum += weight * weight * input_variance[j];
+                            weight_sum += weight;
+                        }
+                    }
+                    // The center tap has a weight of at least 9/64
+                    (
+                        sum.map(|s| s / weight_sum),
+                        variance_sum / (weight_sum * weight_sum),
+                    )
+                })
+                .collect();
+            (irradiance, variance) = filtered.into_iter().unzip();
+        }
+
+        irradiance
+            .iter()
+            .zip(&divisor)
+            .map(|(&[r, g, b], d)| Color::new(r, g, b) * *d)
+            .collect()
+    }
+
+    /// Replace the radiance of the film by its denoised version. The film must
+    /// hold the output variables, which provide the albedo and normal.
+    ///
+    /// Emitters and the background seen straight from the camera are left out
+    /// of the filter, which would otherwise spread them over their
+    /// surroundings along with the noise, and added back afterwards.
+    pub fn denoise_film(&self, film: &mut Film) {
+        assert!(film.has_aovs(), "denoising needs the albedo and normal");
+        let (width, height) = (film.width(), film.height());
+        let aovs: Vec<AovValues> = (0..width * height)
+            .map(|i| film.aov(i % width, i / width).unwrap_or_default())
+            .collect();
+        let color: Vec<Color> = film
+            .pixels()
+            .iter()
+            .zip(&aovs)
+            .map(|(px, aov)| {
+                let [r, g, b] = px.color().channels();
+                let [er, eg, eb] = aov.emission.channels();
+                Color::new(r - er, g - eg, b - eb)
+            })
+            .collect();
+        // Variance of the mean luminance. A single sample is taken to be as
+        // uncertain as its own value.
+        let variance: Vec<f32> = film
+            .pixels()
+            .iter()
+            .map(|px| match px.samples() {
+                0 => 0.0,
+                1 => px.color().luminance().powi(2),
+                n => px.variance() / n as f32,
+            })
+            .collect();
+        let albedo: Vec<Color> = aovs.iter().map(|aov| aov.albedo).collect();
+        let normal: Vec<Vec3<f32>> = aovs.iter().map(|aov| aov.normal).collect();
+        let denoised = self.denoise((width, height), &color, &variance, &albedo, &normal);
+        for (i, (color, aov)) in denoised.into_iter().zip(&aovs).enumerate() {
+            film.pixel_mut(i % width, i / width)
+                .set_color(color + aov.emission);
+        }
+    }
+}
+
+fn luminance(&[r, g, b]: &[f32; 3]) -> f32 {
+    Color::new(r, g, b).luminance()
+}
+
+/// Gaussian falloff of the squared distance between `a` and `b`.
+fn edge_weight(a: &[f32; 3], b: &[f32; 3], sigma: f32) -> f32 {
+    let distance_squared: f32 = a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum();
+    f32::exp(-distance_squared / (sigma * sigma))
+}
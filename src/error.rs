@@ -0,0 +1,77 @@
+use crate::{obj::ObjError, output::OutputError, scene_file::SceneFileError};
+use std::{
+    fmt::{self, Display},
+    io,
+    path::PathBuf,
+};
+
+/// Result of the fallible operations of the library.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error returned by the library, leaving it to the caller whether to carry on
+/// or exit.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file or directory failed.
+    Io { path: PathBuf, error: io::Error },
+    /// An image could not be encoded or written.
+    Output(OutputError),
+    /// A scene file could not be read or parsed, or one of the files it refers
+    /// to could not be loaded.
+    SceneFile(SceneFileError),
+    /// A mesh could not be loaded.
+    Mesh(ObjError),
+    /// No built-in scene has the given name.
+    UnknownScene(String),
+    /// The render settings cannot be rendered with, for the given reason.
+    InvalidSettings(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => {
+                write!(f, "unable to access {}: {}", path.display(), error)
+            }
+            Self::Output(error) => write!(f, "{}", error),
+            Self::SceneFile(error) => write!(f, "{}", error),
+            Self::Mesh(error) => write!(f, "unable to load mesh: {}", error),
+            Self::UnknownScene(name) => write!(
+                f,
+                "scene name {} not available [options: basic, complex, cornell]",
+                name
+            ),
+            Self::InvalidSettings(reason) => write!(f, "invalid render settings: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Output(error) => Some(error),
+            Self::SceneFile(error) => Some(error),
+            Self::Mesh(error) => Some(error),
+            Self::UnknownScene(_) | Self::InvalidSettings(_) => None,
+        }
+    }
+}
+
+impl From<OutputError> for Error {
+    fn from(error: OutputError) -> Self {
+        Self::Output(error)
+    }
+}
+
+impl From<SceneFileError> for Error {
+    fn from(error: SceneFileError) -> Self {
+        Self::SceneFile(error)
+    }
+}
+
+impl From<ObjError> for Error {
+    fn from(error: ObjError) -> Self {
+        Self::Mesh(error)
+    }
+}
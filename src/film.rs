@@ -0,0 +1,316 @@
+use crate::{aov::AovValues, color::Color, filter::Filter, misc, tonemap::ToneMap};
+use image::{ImageBuffer, Rgb, Rgb32FImage, RgbImage};
+use rayon::{
+    iter::{IndexedParallelIterator, ParallelIterator},
+    slice::ParallelSliceMut,
+};
+use std::ops::Range;
+
+/// Accumulated radiance estimate of a single pixel.
+///
+/// The radiance is the filtered average of the samples splatted into the
+/// pixel, which include samples taken by its neighbours. The sample count and
+/// the luminance statistics only cover the samples taken by the pixel itself.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FilmPixel {
+    /// Weighted sum of the radiance samples.
+    sum: Color,
+    /// Sum of the weights of every sample.
+    weight_sum: f32,
+    /// Number of samples taken.
+    samples: u32,
+    /// Running mean of the luminance of the samples.
+    mean: f32,
+    /// Sum of the squared deviations of the luminance from its running mean.
+    m2: f32,
+}
+
+impl FilmPixel {
+    /// Account for a sample taken by this pixel in its statistics. The sample
+    /// still has to be splatted to contribute to the radiance.
+    pub fn add_sample(&mut self, radiance: Color) {
+        self.samples += 1;
+
+        // Welford's online update of the mean and variance
+        let luminance = radiance.luminance();
+        let delta = luminance - self.mean;
+        self.mean += delta / self.samples as f32;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    pub fn splat(&mut self, radiance: Color, weight: f32) {
+        self.sum += radiance * weight;
+        self.weight_sum += weight;
+    }
+
+    /// Replace the average radiance of the pixel, keeping its weights and
+    /// statistics, as done by post-processing such as denoising.
+    pub fn set_color(&mut self, color: Color) {
+        self.sum = color * self.weight_sum;
+    }
+
+    /// Weighted average of the radiance samples, or black if the pixel was
+    /// never sampled.
+    pub fn color(&self) -> Color {
+        if self.weight_sum > 0.0 {
+            self.sum / self.weight_sum
+        } else {
+            Color::default()
+        }
+    }
+
+    pub fn weight_sum(&self) -> f32 {
+        self.weight_sum
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Unbiased sample variance of the luminance, or infinity with fewer than
+    /// two samples.
+    pub fn variance(&self) -> f32 {
+        if self.samples > 1 {
+            self.m2 / (self.samples - 1) as f32
+        } else {
+            misc::INFTY
+        }
+    }
+
+    /// Standard error of the mean luminance relative to the mean itself. Dark
+    /// pixels are compared against a luminance of at least `MIN_LUMINANCE`,
+    /// since the same absolute noise is much less visible there.
+    pub fn relative_error(&self) -> f32 {
+        const MIN_LUMINANCE: f32 = 0.01;
+        (self.variance() / self.samples as f32).sqrt() / self.mean.max(MIN_LUMINANCE)
+    }
+}
+
+/// Heap allocated buffer of float pixel accumulators.
+///
+/// Rows are stored from the bottom of the image to the top, matching the
+/// vertical coordinate `v` of the camera. The conversion to an image flips
+/// them back into the usual top to bottom order.
+pub struct Film {
+    width: usize,
+    height: usize,
+    pixels: Vec<FilmPixel>,
+    /// Weighted sums of the output variables, splatted with the same weights
+    /// as the radiance, if they are rendered.
+    aovs: Option<Vec<AovValues>>,
+    /// Object and material identifiers seen through the center of each pixel.
+    ids: Option<Vec<(u32, u32)>>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![FilmPixel::default(); width * height],
+            aovs: None,
+            ids: None,
+        }
+    }
+
+    /// Also accumulate the output variables of the samples.
+    pub fn with_aovs(mut self) -> Self {
+        self.aovs = Some(vec![AovValues::default(); self.width * self.height]);
+        self
+    }
+
+    pub fn has_aovs(&self) -> bool {
+        self.aovs.is_some()
+    }
+
+    /// Weighted average of the output variables of pixel `(x, y)`, if they
+    /// are rendered.
+    pub fn aov(&self, x: usize, y: usize) -> Option<AovValues> {
+        let sum = self.aovs.as_ref()?[x + y * self.width];
+        let weight_sum = self.pixel(x, y).weight_sum();
+        Some(if weight_sum > 0.0 {
+            sum * (1.0 / weight_sum)
+        } else {
+            AovValues::default()
+        })
+    }
+
+    /// Object and material identifiers of pixel `(x, y)`, if they were set.
+    pub fn ids(&self, x: usize, y: usize) -> Option<(u32, u32)> {
+        Some(self.ids.as_ref()?[x + y * self.width])
+    }
+
+    /// Set the object and material identifiers of every pixel, given row by
+    /// row from the bottom of the image.
+    pub fn set_ids(&mut self, ids: Vec<(u32, u32)>) {
+        assert_eq!(ids.len(), self.width * self.height);
+        self.ids = Some(ids);
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Pixel at column `x` and row `y`, counted from the bottom of the image.
+    pub fn pixel(&self, x: usize, y: usize) -> &FilmPixel {
+        &self.pixels[x + y * self.width]
+    }
+
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> &mut FilmPixel {
+        &mut self.pixels[x + y * self.width]
+    }
+
+    pub fn pixels(&self) -> &[FilmPixel] {
+        &self.pixels
+    }
+
+    /// Parallel iterator over the given rows of the film, paired with their
+    /// index counted from the bottom of the image.
+    pub fn par_rows_mut(
+        &mut self,
+        rows: Range<usize>,
+    ) -> impl IndexedParallelIterator<Item = (usize, &mut [FilmPixel])> {
+        let start = rows.start;
+        self.pixels[rows.start * self.width..rows.end * self.width]
+            .par_chunks_mut(self.width)
+            .enumerate()
+            .map(move |(y, row)| (start + y, row))
+    }
+
+    /// Add the samples splatted into `splats` to the radiance of the pixels.
+    pub fn merge(&mut self, splats: &SplatBuffer) {
+        let start = splats.first_row * self.width;
+        let end = start + splats.values.len();
+        for (px, (sum, weight_sum)) in self.pixels[start..end].iter_mut().zip(&splats.values) {
+            px.sum += *sum;
+            px.weight_sum += *weight_sum;
+        }
+        if let (Some(aovs), Some(splatted)) = (&mut self.aovs, &splats.aovs) {
+            for (sum, value) in aovs[start..end].iter_mut().zip(splatted) {
+                *sum += *value;
+            }
+        }
+    }
+
+    /// Convert the film into an 8-bit sRGB image through `tone_map`, flipping
+    /// the rows so that the top of the scene is at the top of the image.
+    pub fn to_rgb_image(&self, tone_map: &ToneMap) -> RgbImage {
+        let max_luminance = self
+            .pixels
+            .iter()
+            .map(|px| px.color().luminance())
+            .fold(0.0, f32::max);
+        ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let color = self.pixel(x as usize, self.height - 1 - y as usize).color();
+            tone_map.apply(color, max_luminance).rgb()
+        })
+    }
+
+    /// Convert the film into an image of linear radiance, with the rows from
+    /// the top of the image to the bottom.
+    pub fn to_rgb32f_image(&self) -> Rgb32FImage {
+        ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let color = self.pixel(x as usize, self.height - 1 - y as usize).color();
+            Rgb(color.channels())
+        })
+    }
+
+    /// Heatmap of the number of samples taken by each pixel, from black for no
+    /// samples through red and yellow to white for the most sampled pixel.
+    pub fn to_sample_heatmap(&self) -> RgbImage {
+        let max_samples = self
+            .pixels
+            .iter()
+            .map(FilmPixel::samples)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let samples = self
+                .pixel(x as usize, self.height - 1 - y as usize)
+                .samples();
+            let t = (samples as f32 / max_samples as f32).clamp(0.0, 1.0);
+            let channel = |start: f32| ((3.0 * t - start).clamp(0.0, 1.0) * 255.0) as u8;
+            Rgb([channel(0.0), channel(1.0), channel(2.0)])
+        })
+    }
+}
+
+/// Weighted radiance splatted by the samples of a row of pixels into the
+/// pixels of the rows around it, to be merged into the film afterwards.
+///
+/// Merging the buffers in a fixed order keeps the sums independent of the
+/// order in which rows are rendered.
+pub struct SplatBuffer {
+    width: usize,
+    first_row: usize,
+    values: Vec<(Color, f32)>,
+    aovs: Option<Vec<AovValues>>,
+}
+
+impl SplatBuffer {
+    /// Buffer for the samples taken by row `row` of a film of the given
+    /// dimensions, filtered with `filter`. Output variables are collected too
+    /// if `with_aovs` is set.
+    pub fn new(
+        (width, height): (usize, usize),
+        row: usize,
+        filter: &dyn Filter,
+        with_aovs: bool,
+    ) -> Self {
+        let reach = (filter.radius() + 0.5).ceil() as usize;
+        let first_row = row.saturating_sub(reach);
+        let end_row = (row + reach + 1).min(height);
+        let len = (end_row - first_row) * width;
+        Self {
+            width,
+            first_row,
+            values: vec![(Color::default(), 0.0); len],
+            aovs: with_aovs.then(|| vec![AovValues::default(); len]),
+        }
+    }
+
+    /// Splat a sample taken by pixel `(x, y)` at offset `jitter` from its
+    /// bottom left corner into every pixel whose center lies within the radius
+    /// of the filter.
+    pub fn add_sample(
+        &mut self,
+        (x, y): (usize, usize),
+        jitter: (f32, f32),
+        radiance: Color,
+        aov: &AovValues,
+        filter: &dyn Filter,
+    ) {
+        let radius = filter.radius();
+        let rows = self.values.len() / self.width;
+        // Pixels `p + k` whose center lies at an offset `offset - k` from the
+        // sample within [-radius, radius), clipped to the buffer. Working with
+        // offsets rather than film positions keeps a sample jittered next to
+        // the edge of its pixel from rounding into the neighbour.
+        let range = |p: usize, offset: f32, first: usize, len: usize| {
+            let lo = (offset - radius).floor() as isize + 1;
+            let hi = (offset + radius).floor() as isize;
+            let lo = lo.max(first as isize - p as isize);
+            let hi = hi.min((first + len) as isize - 1 - p as isize);
+            lo..=hi
+        };
+        let offset = (jitter.0 - 0.5, jitter.1 - 0.5);
+        for k in range(y, offset.1, self.first_row, rows) {
+            let row = (y as isize + k) as usize - self.first_row;
+            for l in range(x, offset.0, 0, self.width) {
+                let weight = filter.evaluate(offset.0 - l as f32, offset.1 - k as f32);
+                let index = row * self.width + (x as isize + l) as usize;
+                let (sum, weight_sum) = &mut self.values[index];
+                *sum += radiance * weight;
+                *weight_sum += weight;
+                if let Some(aovs) = &mut self.aovs {
+                    aovs[index] += *aov * weight;
+                }
+            }
+        }
+    }
+}
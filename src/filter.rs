@@ -0,0 +1,234 @@
+use crate::misc;
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// Pixel reconstruction filter, weighting the samples that fall around the
+/// center of a pixel.
+///
+/// Every filter is separable, with a square support of half-width `radius`
+/// pixels centered on the pixel.
+pub trait Filter: Send + Sync {
+    fn radius(&self) -> f32;
+
+    /// Weight of a sample at offset `(x, y)` from the center of a pixel, for
+    /// offsets within the radius.
+    fn evaluate(&self, x: f32, y: f32) -> f32;
+}
+
+/// Reconstruction filter selectable from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterType {
+    /// Average of the samples inside of each pixel.
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    BlackmanHarris,
+}
+
+impl FilterType {
+    /// Filter with the radius usually chosen for its kind.
+    pub fn build(self) -> Box<dyn Filter> {
+        match self {
+            Self::Box => Box::new(BoxFilter::new(0.5)),
+            Self::Tent => Box::new(TentFilter::new(1.0)),
+            Self::Gaussian => Box::new(GaussianFilter::new(1.5, 0.5)),
+            Self::Mitchell => Box::new(MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0)),
+            Self::BlackmanHarris => Box::new(BlackmanHarrisFilter::new(2.0)),
+        }
+    }
+}
+
+impl FromStr for FilterType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "box" => Ok(Self::Box),
+            "tent" => Ok(Self::Tent),
+            "gaussian" => Ok(Self::Gaussian),
+            "mitchell" => Ok(Self::Mitchell),
+            "blackman-harris" => Ok(Self::BlackmanHarris),
+            _ => Err(format!(
+                concat!(
+                    "unknown filter `{}`, expected `box`, `tent`, `gaussian`, `mitchell` or ",
+                    "`blackman-harris`"
+                ),
+                s
+            )),
+        }
+    }
+}
+
+impl Display for FilterType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Box => "box",
+            Self::Tent => "tent",
+            Self::Gaussian => "gaussian",
+            Self::Mitchell => "mitchell",
+            Self::BlackmanHarris => "blackman-harris",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Constant weight over the whole support.
+pub struct BoxFilter {
+    radius: f32,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f32) -> Self {
+        Self { radius }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, _x: f32, _y: f32) -> f32 {
+        1.0
+    }
+}
+
+/// Weight falling linearly from the center to the edges of the support.
+pub struct TentFilter {
+    radius: f32,
+}
+
+impl TentFilter {
+    pub fn new(radius: f32) -> Self {
+        Self { radius }
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        f32::max(0.0, self.radius - x.abs())
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+}
+
+/// Gaussian of standard deviation `sigma`, shifted down so that it reaches
+/// zero at the edges of the support.
+pub struct GaussianFilter {
+    radius: f32,
+    sigma: f32,
+    edge: f32,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f32, sigma: f32) -> Self {
+        Self {
+            radius,
+            sigma,
+            edge: gaussian(radius, sigma),
+        }
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        f32::max(0.0, gaussian(x, self.sigma) - self.edge)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+}
+
+fn gaussian(x: f32, sigma: f32) -> f32 {
+    f32::exp(-x * x / (2.0 * sigma * sigma))
+}
+
+/// Mitchell–Netravali cubic with parameters `b` and `c`, stretched over the
+/// support. Its negative lobes sharpen the image, at the cost of some ringing
+/// around strong edges.
+pub struct MitchellFilter {
+    radius: f32,
+    b: f32,
+    c: f32,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f32, b: f32, c: f32) -> Self {
+        Self { radius, b, c }
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let (b, c) = (self.b, self.c);
+        // The cubic is defined over [-2, 2]
+        let x = (2.0 * x / self.radius).abs();
+        let value = if x < 1.0 {
+            (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                + (6.0 - 2.0 * b)
+        } else if x < 2.0 {
+            (-b - 6.0 * c) * x.powi(3)
+                + (6.0 * b + 30.0 * c) * x.powi(2)
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c)
+        } else {
+            0.0
+        };
+        value / 6.0
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+}
+
+/// Four-term Blackman–Harris window spread over the support, close to a
+/// Gaussian but with much lower side lobes.
+pub struct BlackmanHarrisFilter {
+    radius: f32,
+}
+
+impl BlackmanHarrisFilter {
+    pub fn new(radius: f32) -> Self {
+        Self { radius }
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        const A0: f32 = 0.35875;
+        const A1: f32 = 0.48829;
+        const A2: f32 = 0.14128;
+        const A3: f32 = 0.01168;
+        let t = 2.0 * misc::PI * (x / (2.0 * self.radius) + 0.5);
+        A0 - A1 * t.cos() + A2 * (2.0 * t).cos() - A3 * (3.0 * t).cos()
+    }
+}
+
+impl Filter for BlackmanHarrisFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+}
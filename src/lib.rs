@@ -1,10 +1,29 @@
+pub mod aabb;
+pub mod aov;
+pub mod background;
+pub mod bvh;
 pub mod camera;
 pub mod color;
+pub mod denoise;
+pub mod error;
+pub mod film;
+pub mod filter;
 pub mod material;
+pub mod mesh;
 pub mod misc;
+pub mod obj;
+pub mod output;
+pub mod progress;
 pub mod ray;
 pub mod rendering;
+pub mod sampler;
 pub mod scene;
+pub mod scene_file;
+pub mod settings;
 pub mod surf;
 pub mod surf_list;
+pub mod tonemap;
+pub mod triangle;
 pub mod vec3;
+
+pub use error::{Error, Result};
@@ -1,78 +1,498 @@
-use clap::{Arg, Command as ClapCommand};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use log::LevelFilter;
 use radiant::{
-    camera::{self, Camera},
-    misc::{self, LogLevel},
-    rendering, scene,
+    aov::Aov,
+    camera::FocusDistance,
+    filter::FilterType,
+    misc::{self, LogLevel, Rng, StderrLogger},
+    output::{Output, OutputFormat},
+    progress::{CancellationToken, IndicatifProgress, ProgressSink, SilentProgress},
+    rendering,
+    sampler::SamplerType,
+    scene::{self, SceneType},
+    scene_file::SceneFile,
+    settings::{self, RenderSettings},
+    tonemap::{ToneMap, ToneMapOperator},
     vec3::Vec3,
 };
-use std::{fs, path::PathBuf, process::Command};
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Command opening the rendered image when `RADIANT_VIEWER` is not set.
+const DEFAULT_VIEWER: &str = "xdg-open";
 
 fn main() {
     let matches = ClapCommand::new("radiant")
         .arg(
             Arg::new("outdir")
                 .short('o')
+                .long("outdir")
                 .default_value("./output/")
                 .help("Output directory path."),
         )
         .arg(
             Arg::new("filename")
                 .short('n')
+                .long("filename")
                 .default_value("out")
-                .help("Output file name, without extensions."),
+                .help(concat!(
+                    "Output file name. An extension of `png`, `exr`, `pfm` or `hdr` selects the ",
+                    "format, unless `--format` replaces it; otherwise the one of `--format` is added."
+                )),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_parser(value_parser!(OutputFormat))
+                .help(format!(
+                    concat!(
+                        "Format of the output image: `png`, or `exr`, `pfm` and `hdr` for linear ",
+                        "float radiance along with a PNG preview [default: {}]."
+                    ),
+                    OutputFormat::default()
+                )),
+        )
+        .arg(
+            Arg::new("exposure")
+                .long("exposure")
+                .value_parser(value_parser!(f32))
+                .allow_hyphen_values(true)
+                .help(format!(
+                    "Exposure of the PNG image in stops, before tone mapping [default: {}].",
+                    ToneMap::default().exposure
+                )),
+        )
+        .arg(
+            Arg::new("tonemap")
+                .long("tonemap")
+                .value_parser(value_parser!(ToneMapOperator))
+                .help(format!(
+                    concat!(
+                        "Tone mapping of the PNG image: `linear`, `reinhard`, `extended-reinhard`, ",
+                        "`aces` or `hable` [default: {}]."
+                    ),
+                    ToneMapOperator::default()
+                )),
+        )
+        .arg(
+            Arg::new("half")
+                .long("half")
+                .action(ArgAction::SetTrue)
+                .help("Store OpenEXR channels as half instead of single precision floats."),
+        )
+        .arg(
+            Arg::new("aov")
+                .long("aov")
+                .value_parser(value_parser!(Aov))
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .help(concat!(
+                    "Auxiliary outputs to write, separated by commas: `albedo`, `normal`, ",
+                    "`depth`, `position`, `object_id`, `material_id`, `direct` or `indirect`. ",
+                    "OpenEXR files store them as extra channels, other formats as separate images."
+                )),
         )
         .arg(
             Arg::new("scene")
                 .short('s')
+                .long("scene")
                 .default_value("basic")
-                .help("Use a default scene. Possible values are `basic` and `complex`."),
+                .help("Use a default scene. Possible values are `basic`, `complex` and `cornell`."),
+        )
+        .arg(
+            Arg::new("scene-file")
+                .long("scene-file")
+                .value_parser(value_parser!(PathBuf))
+                .conflicts_with("scene")
+                .help("Load the scene, camera and render settings from a TOML file."),
+        )
+        .arg(
+            Arg::new("width")
+                .long("width")
+                .value_parser(value_parser!(u32).range(2..))
+                .help(format!(
+                    "Width of the output image in pixels [default: {}].",
+                    settings::DEFAULT_IMAGE_WIDTH
+                )),
+        )
+        .arg(
+            Arg::new("height")
+                .long("height")
+                .value_parser(value_parser!(u32).range(2..))
+                .help(format!(
+                    "Height of the output image in pixels [default: {}].",
+                    settings::DEFAULT_IMAGE_HEIGHT
+                )),
+        )
+        .arg(
+            Arg::new("spp")
+                .long("spp")
+                .value_parser(value_parser!(u32).range(1..))
+                .help(format!(
+                    "Number of samples per pixel [default: {}].",
+                    settings::DEFAULT_SAMPLES_PER_PIXEL
+                )),
+        )
+        .arg(
+            Arg::new("max-depth")
+                .long("max-depth")
+                .value_parser(value_parser!(u32).range(1..))
+                .help(format!(
+                    "Maximum number of bounces of a ray [default: {}].",
+                    settings::DEFAULT_MAX_DEPTH
+                )),
+        )
+        .arg(
+            Arg::new("rr-depth")
+                .long("rr-depth")
+                .value_parser(value_parser!(u32))
+                .help(format!(
+                    "Bounces after which paths are ended by Russian roulette [default: {}].",
+                    settings::DEFAULT_RR_DEPTH
+                )),
+        )
+        .arg(
+            Arg::new("noise-threshold")
+                .long("noise-threshold")
+                .value_parser(value_parser!(f32))
+                .help(concat!(
+                    "Sample adaptively, stopping pixels once the relative error of their mean ",
+                    "falls below this threshold; `--spp` becomes the average budget."
+                )),
+        )
+        .arg(
+            Arg::new("min-spp")
+                .long("min-spp")
+                .value_parser(value_parser!(u32).range(1..))
+                .help(format!(
+                    "Samples every pixel takes when sampling adaptively [default: {}].",
+                    settings::DEFAULT_MIN_SPP
+                )),
+        )
+        .arg(
+            Arg::new("max-spp")
+                .long("max-spp")
+                .value_parser(value_parser!(u32).range(1..))
+                .help(format!(
+                    "Most samples a pixel takes when sampling adaptively [default: {}].",
+                    settings::DEFAULT_MAX_SPP
+                )),
+        )
+        .arg(
+            Arg::new("spp-heatmap")
+                .long("spp-heatmap")
+                .action(ArgAction::SetTrue)
+                .help("Also write an image of the samples taken per pixel, suffixed with `_spp`."),
+        )
+        .arg(
+            Arg::new("denoise")
+                .long("denoise")
+                .action(ArgAction::SetTrue)
+                .help("Filter the noise out of the image, guided by the albedo and normal."),
+        )
+        .arg(
+            Arg::new("sampler")
+                .long("sampler")
+                .value_parser(value_parser!(SamplerType))
+                .help(format!(
+                    concat!(
+                        "Sampler of the pixel, lens and bounce sample values: `independent`, ",
+                        "`stratified`, `halton` or `sobol` [default: {}]."
+                    ),
+                    SamplerType::default()
+                )),
+        )
+        .arg(
+            Arg::new("filter")
+                .long("filter")
+                .value_parser(value_parser!(FilterType))
+                .help(format!(
+                    concat!(
+                        "Filter splatting the samples into the pixels around them: `box`, `tent`, ",
+                        "`gaussian`, `mitchell` or `blackman-harris` [default: {}]."
+                    ),
+                    FilterType::default()
+                )),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u32))
+                .help(format!(
+                    "Seed of the random numbers; equal seeds give identical renders [default: {}].",
+                    settings::DEFAULT_SEED
+                )),
+        )
+        .arg(
+            Arg::new("lookfrom")
+                .long("lookfrom")
+                .value_parser(value_parser!(Vec3<f32>))
+                .allow_hyphen_values(true)
+                .help("Point the camera looks from, given as `x,y,z`."),
+        )
+        .arg(
+            Arg::new("lookat")
+                .long("lookat")
+                .value_parser(value_parser!(Vec3<f32>))
+                .allow_hyphen_values(true)
+                .help("Point the camera looks at, given as `x,y,z`."),
+        )
+        .arg(
+            Arg::new("vup")
+                .long("vup")
+                .value_parser(value_parser!(Vec3<f32>))
+                .allow_hyphen_values(true)
+                .help("Up direction of the camera, given as `x,y,z`."),
+        )
+        .arg(
+            Arg::new("fov")
+                .long("fov")
+                .value_parser(value_parser!(f32))
+                .help("Vertical field-of-view of the camera in degrees."),
+        )
+        .arg(
+            Arg::new("aperture")
+                .long("aperture")
+                .value_parser(value_parser!(f32))
+                .help("Aperture of the camera lens, zero for a pinhole camera."),
+        )
+        .arg(
+            Arg::new("focus-dist")
+                .long("focus-dist")
+                .value_parser(value_parser!(FocusDistance))
+                .help("Distance of focus, or `auto` to focus on the point the camera looks at."),
+        )
+        .arg(
+            Arg::new("open")
+                .long("open")
+                .action(ArgAction::SetTrue)
+                .overrides_with("no-open")
+                .help(concat!(
+                    "Open the image once rendered, with the command in `RADIANT_VIEWER` or else ",
+                    "`xdg-open`. By default, it is opened only from a terminal with a display."
+                )),
+        )
+        .arg(
+            Arg::new("no-open")
+                .long("no-open")
+                .action(ArgAction::SetTrue)
+                .overrides_with("open")
+                .help("Do not open the image once rendered."),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .action(ArgAction::Count)
+                .help("Also print debugging messages."),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .action(ArgAction::Count)
+                .help(concat!(
+                    "Hide the progress bar and the informational messages, or with `-qq` the ",
+                    "warnings too."
+                )),
         )
         .get_matches();
 
+    let verbosity = matches.get_count("verbose") as i32 - matches.get_count("quiet") as i32;
+    let level = match verbosity {
+        i32::MIN..=-2 => LevelFilter::Error,
+        -1 => LevelFilter::Warn,
+        0 => LevelFilter::Info,
+        _ => LevelFilter::Debug,
+    };
+    if let Err(e) = StderrLogger::new().init(level) {
+        eprintln!("Unable to set up logging: {}", e);
+    }
+
+    if let Err(e) = run(&matches) {
+        misc::log(LogLevel::Fatal, &e.to_string());
+        std::process::exit(-1);
+    }
+}
+
+fn run(matches: &ArgMatches) -> radiant::Result<()> {
+    let (mut scene, mut settings) = match matches.get_one::<PathBuf>("scene-file") {
+        Some(scene_path) => {
+            let scene_file = SceneFile::load(scene_path)?;
+            (scene_file.scene, scene_file.settings)
+        }
+        None => {
+            let scene_type: SceneType = matches.get_one::<String>("scene").unwrap().parse()?;
+            let seed = matches
+                .get_one::<u32>("seed")
+                .map_or(RenderSettings::default().seed, |seed| *seed);
+            (
+                scene::make_scene(scene_type, &mut Rng::new(seed as u64)),
+                RenderSettings::default(),
+            )
+        }
+    };
+    if let Some(width) = matches.get_one::<u32>("width") {
+        settings.image_width = *width as usize;
+    }
+    if let Some(height) = matches.get_one::<u32>("height") {
+        settings.image_height = *height as usize;
+    }
+    if let Some(spp) = matches.get_one::<u32>("spp") {
+        settings.samples_per_pixel = *spp;
+    }
+    if let Some(max_depth) = matches.get_one::<u32>("max-depth") {
+        settings.max_depth = *max_depth;
+    }
+    if let Some(rr_depth) = matches.get_one::<u32>("rr-depth") {
+        settings.rr_depth = *rr_depth;
+    }
+    if let Some(seed) = matches.get_one::<u32>("seed") {
+        settings.seed = *seed;
+    }
+    if let Some(sampler) = matches.get_one::<SamplerType>("sampler") {
+        settings.sampler = *sampler;
+    }
+    if let Some(filter) = matches.get_one::<FilterType>("filter") {
+        settings.filter = *filter;
+    }
+    if let Some(threshold) = matches.get_one::<f32>("noise-threshold") {
+        settings.noise_threshold = Some(*threshold);
+    }
+    if let Some(min_spp) = matches.get_one::<u32>("min-spp") {
+        settings.min_spp = *min_spp;
+    }
+    if let Some(max_spp) = matches.get_one::<u32>("max-spp") {
+        settings.max_spp = *max_spp;
+    }
+    settings.spp_heatmap = matches.get_flag("spp-heatmap");
+    if matches.get_flag("denoise") {
+        settings.denoise = true;
+    }
+    if let Some(lookfrom) = matches.get_one::<Vec3<f32>>("lookfrom") {
+        scene.camera.lookfrom = *lookfrom;
+    }
+    if let Some(lookat) = matches.get_one::<Vec3<f32>>("lookat") {
+        scene.camera.lookat = *lookat;
+    }
+    if let Some(vup) = matches.get_one::<Vec3<f32>>("vup") {
+        scene.camera.vup = *vup;
+    }
+    if let Some(fov) = matches.get_one::<f32>("fov") {
+        scene.camera.vfov = *fov;
+    }
+    if let Some(aperture) = matches.get_one::<f32>("aperture") {
+        scene.camera.aperture = *aperture;
+    }
+    if let Some(focus_dist) = matches.get_one::<FocusDistance>("focus-dist") {
+        scene.camera.focus_dist = *focus_dist;
+    }
+    settings.validate()?;
+
     let mut path = PathBuf::new();
     path.push(matches.get_one::<String>("outdir").unwrap());
     if !path.is_dir() {
-        if let Err(e) = fs::create_dir(path.as_path()) {
-            misc::log(
-                LogLevel::Fatal,
-                &format!("Unable to create output directory due to error {}", e),
-            );
-            std::process::exit(-1);
-        }
+        fs::create_dir(&path).map_err(|error| radiant::Error::Io {
+            path: path.clone(),
+            error,
+        })?;
     }
     path.push(matches.get_one::<String>("filename").unwrap());
-    path.set_extension("png");
+    let format = matches
+        .get_one::<OutputFormat>("format")
+        .copied()
+        .or_else(|| OutputFormat::from_path(&path))
+        .unwrap_or_default();
+    // The extension of another format is replaced, while any other one is
+    // taken to be part of the name
+    match OutputFormat::from_path(&path) {
+        Some(f) if f == format => {}
+        Some(_) => {
+            path.set_extension(format.extension());
+        }
+        None => {
+            let mut name = path.into_os_string();
+            name.push(".");
+            name.push(format.extension());
+            path = name.into();
+        }
+    }
+    let output = Output {
+        path,
+        format,
+        half: matches.get_flag("half"),
+        tone_map: ToneMap::new(
+            matches
+                .get_one::<ToneMapOperator>("tonemap")
+                .copied()
+                .unwrap_or_default(),
+            matches
+                .get_one::<f32>("exposure")
+                .copied()
+                .unwrap_or(ToneMap::default().exposure),
+        ),
+        aovs: matches
+            .get_many::<Aov>("aov")
+            .map(|aovs| aovs.copied().collect())
+            .unwrap_or_default(),
+    };
     misc::log(
         LogLevel::Debug,
-        &format!("Output path: {}", path.as_path().display()),
-    );
-
-    // Create a camera
-    let lookfrom = Vec3::new(13.0, 2.0, 3.0);
-    let lookat = Vec3::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
-    let camera = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        20.0,
-        camera::ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
+        &format!("Output path: {}", output.path.display()),
     );
 
+    let progress: Box<dyn ProgressSink> = if matches.get_count("quiet") > 0 {
+        Box::new(SilentProgress)
+    } else {
+        Box::new(IndicatifProgress::new())
+    };
     rendering::render(
-        path.as_path(),
-        camera,
-        scene::SceneType::from(&matches.get_one::<String>("scene").unwrap()),
-    );
+        &output,
+        scene,
+        &settings,
+        progress.as_ref(),
+        &CancellationToken::new(),
+    )?;
+
+    let open = if matches.get_flag("open") {
+        true
+    } else if matches.get_flag("no-open") {
+        false
+    } else {
+        can_show_image()
+    };
+    if open {
+        open_image(&output.preview_path());
+    }
+    Ok(())
+}
+
+/// Whether the program runs from a terminal of a graphical session, where
+/// someone can look at the image.
+fn can_show_image() -> bool {
+    let has_display = ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|var| env::var_os(var).is_some_and(|value| !value.is_empty()));
+    has_display && io::stderr().is_terminal()
+}
 
-    if let Err(e) = Command::new("xdg-open").arg(path).spawn() {
+/// Open `path` with the viewer named by `RADIANT_VIEWER`, possibly followed by
+/// arguments, or else with `xdg-open`. An empty variable disables the viewer.
+fn open_image(path: &Path) {
+    let viewer = env::var("RADIANT_VIEWER").unwrap_or_else(|_| DEFAULT_VIEWER.to_string());
+    let mut words = viewer.split_whitespace();
+    let program = match words.next() {
+        Some(program) => program,
+        None => return,
+    };
+    if let Err(e) = Command::new(program).args(words).arg(path).spawn() {
         misc::log(
-            LogLevel::Error,
-            &format!("xdg-open failed to execute, {}", e),
+            LogLevel::Warning,
+            &format!("{} failed to execute, {}", program, e),
         );
     }
 }
@@ -1,9 +1,32 @@
-use crate::{color::Color, misc, ray::Ray, surf::HitRecord, vec3::Vec3};
-use rand;
+use crate::{color::Color, misc, ray::Ray, sampler, surf::HitRecord};
 
 pub trait Scatterable {
-    // How the ray interacts with the material
-    fn scatter(&self, ray: Ray, hit_record: HitRecord) -> Option<(Ray, Color)>;
+    // How the ray interacts with the material, given the sample values `u`
+    // and `uv` uniformly distributed in [0, 1)
+    fn scatter(
+        &self,
+        ray: Ray,
+        hit_record: HitRecord,
+        u: f32,
+        uv: (f32, f32),
+    ) -> Option<(Ray, Color)>;
+
+    // Light given off by the material at the hit point
+    fn emitted(&self, _hit_record: &HitRecord) -> Color {
+        Color::default()
+    }
+
+    // Density, with respect to solid angle, with which `scatter` picks the
+    // direction of `scattered`. Specular materials, which scatter towards a
+    // single direction, have no density and cannot be lit by light sampling.
+    fn scattering_pdf(&self, _ray: &Ray, _hit_record: &HitRecord, _scattered: &Ray) -> Option<f32> {
+        None
+    }
+}
+
+fn with_scalar(color: Color, scalar: f32) -> [f32; 4] {
+    let [r, g, b] = color.channels();
+    [r, g, b, scalar]
 }
 
 #[derive(Clone, Copy)]
@@ -11,21 +34,84 @@ pub enum Material {
     Lambertian(Lambertian),
     Metal(Metal),
     Dielectric(Dielectric),
+    DiffuseLight(DiffuseLight),
 }
 
-impl Material {
-    pub fn default() -> Self {
+impl Default for Material {
+    fn default() -> Self {
         Self::Lambertian(Lambertian::new(Color::new(0.0, 0.0, 0.0)))
     }
+}
+
+impl Material {
+    pub fn scatter(
+        &self,
+        ray: Ray,
+        rec: HitRecord,
+        u: f32,
+        uv: (f32, f32),
+    ) -> Option<(Ray, Color)> {
+        match self {
+            Self::Lambertian(l) => l.scatter(ray, rec, u, uv),
+            Self::Metal(m) => m.scatter(ray, rec, u, uv),
+            Self::Dielectric(d) => d.scatter(ray, rec, u, uv),
+            Self::DiffuseLight(l) => l.scatter(ray, rec, u, uv),
+        }
+    }
+
+    pub fn emitted(&self, rec: &HitRecord) -> Color {
+        match self {
+            Self::Lambertian(l) => l.emitted(rec),
+            Self::Metal(m) => m.emitted(rec),
+            Self::Dielectric(d) => d.emitted(rec),
+            Self::DiffuseLight(l) => l.emitted(rec),
+        }
+    }
+
+    pub fn scattering_pdf(&self, ray: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<f32> {
+        match self {
+            Self::Lambertian(l) => l.scattering_pdf(ray, rec, scattered),
+            Self::Metal(m) => m.scattering_pdf(ray, rec, scattered),
+            Self::Dielectric(d) => d.scattering_pdf(ray, rec, scattered),
+            Self::DiffuseLight(l) => l.scattering_pdf(ray, rec, scattered),
+        }
+    }
 
-    pub fn scatter(&self, ray: Ray, rec: HitRecord) -> Option<(Ray, Color)> {
+    /// Fraction of the light reflected or transmitted by the material, used
+    /// as a guide for compositing and denoising. Emitters report their color
+    /// scaled down to at most one.
+    pub fn albedo(&self) -> Color {
         match self {
-            Self::Lambertian(l) => l.scatter(ray, rec),
-            Self::Metal(m) => m.scatter(ray, rec),
-            Self::Dielectric(d) => d.scatter(ray, rec),
+            Self::Lambertian(l) => l.albedo,
+            Self::Metal(m) => m.albedo,
+            Self::Dielectric(_) => Color::new(1.0, 1.0, 1.0),
+            Self::DiffuseLight(l) => l.emit / l.emit.max_component().max(1.0),
         }
     }
 
+    /// Identifier of the material, hashed from its kind and parameters so
+    /// that equal materials share the same identifier.
+    pub fn id(&self) -> u32 {
+        let (kind, [a, b, c, d]) = match self {
+            Self::Lambertian(l) => (1, with_scalar(l.albedo, 0.0)),
+            Self::Metal(m) => (2, with_scalar(m.albedo, m.fuzz)),
+            Self::Dielectric(d) => (3, [d.index_refraction, 0.0, 0.0, 0.0]),
+            Self::DiffuseLight(l) => (4, with_scalar(l.emit, 0.0)),
+        };
+        // FNV-1a over the kind and the bits of the parameters
+        [kind, a.to_bits(), b.to_bits(), c.to_bits(), d.to_bits()]
+            .iter()
+            .fold(0x811c9dc5, |hash, word| {
+                word.to_le_bytes().iter().fold(hash, |hash, byte| {
+                    (hash ^ *byte as u32).wrapping_mul(0x01000193)
+                })
+            })
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        matches!(self, Self::DiffuseLight(_))
+    }
+
     pub fn lambertian(albedo: Color) -> Self {
         Self::Lambertian(Lambertian::new(albedo))
     }
@@ -37,6 +123,10 @@ impl Material {
     pub fn dielectric(index_refraction: f32) -> Self {
         Self::Dielectric(Dielectric::new(index_refraction))
     }
+
+    pub fn diffuse_light(emit: Color) -> Self {
+        Self::DiffuseLight(DiffuseLight::new(emit))
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -51,8 +141,8 @@ impl Lambertian {
 }
 
 impl Scatterable for Lambertian {
-    fn scatter(&self, _ray: Ray, rec: HitRecord) -> Option<(Ray, Color)> {
-        let mut direction = rec.normal() + Vec3::random_unit_vector(&mut rand::thread_rng());
+    fn scatter(&self, _ray: Ray, rec: HitRecord, _u: f32, uv: (f32, f32)) -> Option<(Ray, Color)> {
+        let mut direction = rec.normal() + sampler::uniform_sphere(uv);
         // Degenerate scatter direction
         if direction.near_zero() {
             direction = rec.normal();
@@ -60,6 +150,12 @@ impl Scatterable for Lambertian {
         let scattered = Ray::new(rec.point(), direction);
         Some((scattered, self.albedo))
     }
+
+    // Cosine weighted hemisphere around the normal
+    fn scattering_pdf(&self, _ray: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<f32> {
+        let cos = rec.normal().dot(&scattered.direction().unit_vector());
+        Some(f32::max(cos, 0.0) / misc::PI)
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -76,11 +172,11 @@ impl Metal {
 }
 
 impl Scatterable for Metal {
-    fn scatter(&self, ray: Ray, rec: HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, ray: Ray, rec: HitRecord, u: f32, uv: (f32, f32)) -> Option<(Ray, Color)> {
         let reflected = ray.direction().unit_vector().reflect(&rec.normal());
         let scattered = Ray::new(
             rec.point(),
-            reflected + Vec3::random_unit_sphere(&mut rand::thread_rng()) * self.fuzz,
+            reflected + sampler::uniform_ball(u, uv) * self.fuzz,
         );
 
         if scattered.direction().dot(&rec.normal()) > 0.0 {
@@ -108,7 +204,7 @@ impl Dielectric {
 }
 
 impl Scatterable for Dielectric {
-    fn scatter(&self, ray: Ray, rec: HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, ray: Ray, rec: HitRecord, u: f32, _uv: (f32, f32)) -> Option<(Ray, Color)> {
         let attenuation = Color::new(1.0, 1.0, 1.0);
         let refraction_ratio = if rec.front_face() {
             1.0 / self.index_refraction
@@ -121,7 +217,7 @@ impl Scatterable for Dielectric {
         let cos_theta = f32::min(-unit_direction.dot(&rec.normal()), 1.0);
         let sin_theta = f32::sqrt(1.0 - cos_theta * cos_theta);
         let direction = if refraction_ratio * sin_theta > 1.0
-            || Self::reflectance(cos_theta, refraction_ratio) > misc::rand()
+            || Self::reflectance(cos_theta, refraction_ratio) > u
         {
             // Reflection occurs
             unit_direction.reflect(&rec.normal())
@@ -133,3 +229,32 @@ impl Scatterable for Dielectric {
         Some((Ray::new(rec.point(), direction), attenuation))
     }
 }
+
+/// Emits light uniformly in every direction from both of its faces, without
+/// scattering any incoming light.
+#[derive(Clone, Copy)]
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    fn new(emit: Color) -> Self {
+        Self { emit }
+    }
+}
+
+impl Scatterable for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray: Ray,
+        _rec: HitRecord,
+        _u: f32,
+        _uv: (f32, f32),
+    ) -> Option<(Ray, Color)> {
+        None
+    }
+
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        self.emit
+    }
+}
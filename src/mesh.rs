@@ -0,0 +1,192 @@
+use crate::{
+    aabb::Aabb,
+    material::Material,
+    ray::Ray,
+    surf::{HitRecord, Surface},
+    surf_list::SurfList,
+    triangle,
+    vec3::Vec3,
+};
+use std::sync::Arc;
+
+/// Indexed triangle mesh.
+///
+/// Every three consecutive entries of the index buffer are the vertices of a
+/// triangle, in counter-clockwise order when seen from its front face. Normals
+/// and texture coordinates, when present, are given per vertex and are
+/// interpolated across each triangle.
+pub struct TriangleMesh {
+    positions: Vec<Vec3<f32>>,
+    indices: Vec<u32>,
+    normals: Option<Vec<Vec3<f32>>>,
+    uvs: Option<Vec<(f32, f32)>>,
+    material: Material,
+}
+
+impl TriangleMesh {
+    /// # Panics
+    ///
+    /// Panics if the number of indices is not a multiple of three or if an
+    /// index is out of the bounds of the vertex buffer.
+    // `usize::is_multiple_of` is only stable since Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn new(positions: Vec<Vec3<f32>>, indices: Vec<u32>, material: Material) -> Self {
+        assert!(
+            indices.len() % 3 == 0,
+            "The number of indices of a triangle mesh should be a multiple of three"
+        );
+        assert!(
+            indices.iter().all(|&i| (i as usize) < positions.len()),
+            "Triangle mesh index out of bounds of the vertex buffer"
+        );
+        Self {
+            positions,
+            indices,
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+
+    /// Use per-vertex normals for smooth shading.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there isn't exactly one normal per vertex.
+    pub fn with_normals(mut self, normals: Vec<Vec3<f32>>) -> Self {
+        assert_eq!(
+            normals.len(),
+            self.positions.len(),
+            "A triangle mesh needs exactly one normal per vertex"
+        );
+        self.normals = Some(normals.into_iter().map(|n| n.unit_vector()).collect());
+        self
+    }
+
+    /// Use per-vertex texture coordinates.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there aren't exactly one pair of coordinates per vertex.
+    pub fn with_uvs(mut self, uvs: Vec<(f32, f32)>) -> Self {
+        assert_eq!(
+            uvs.len(),
+            self.positions.len(),
+            "A triangle mesh needs exactly one pair of texture coordinates per vertex"
+        );
+        self.uvs = Some(uvs);
+        self
+    }
+
+    /// Use `material` for every triangle of the mesh.
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    /// Uniformly scale the mesh by `scale` about the origin and then move it
+    /// by `translation`.
+    pub fn transformed(mut self, scale: f32, translation: Vec3<f32>) -> Self {
+        for p in self.positions.iter_mut() {
+            *p = *p * scale + translation;
+        }
+        self
+    }
+
+    /// Number of triangles in the mesh.
+    pub fn len(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+
+    /// Split the mesh into its triangles, all of which share the buffers of the
+    /// mesh. Adding them to the world one by one lets the BVH separate them.
+    pub fn into_triangles(self) -> Vec<MeshTriangle> {
+        let mesh = Arc::new(self);
+        (0..mesh.len())
+            .map(|index| MeshTriangle {
+                mesh: Arc::clone(&mesh),
+                index,
+            })
+            .collect()
+    }
+
+    /// Add every triangle of the mesh to the `list`, as a single object.
+    pub fn add_to(self, list: &mut SurfList) {
+        list.add_object(self.into_triangles());
+    }
+
+    fn vertex_indices(&self, triangle: usize) -> [usize; 3] {
+        let i = 3 * triangle;
+        [
+            self.indices[i] as usize,
+            self.indices[i + 1] as usize,
+            self.indices[i + 2] as usize,
+        ]
+    }
+}
+
+/// Single triangle of a `TriangleMesh`.
+#[derive(Clone)]
+pub struct MeshTriangle {
+    mesh: Arc<TriangleMesh>,
+    index: usize,
+}
+
+impl MeshTriangle {
+    pub fn vertices(&self) -> [Vec3<f32>; 3] {
+        let [i0, i1, i2] = self.mesh.vertex_indices(self.index);
+        let p = &self.mesh.positions;
+        [p[i0], p[i1], p[i2]]
+    }
+}
+
+impl Surface for MeshTriangle {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        let [i0, i1, i2] = self.mesh.vertex_indices(self.index);
+        let [v0, v1, v2] = self.vertices();
+        let (t, b1, b2) = match triangle::intersect(ray, v0, v1, v2, t_min, t_max) {
+            Some(hit) => hit,
+            None => return false,
+        };
+        let b0 = 1.0 - b1 - b2;
+
+        let outward_normal = (v1 - v0).cross(&(v2 - v0)).unit_vector();
+        rec.record(ray, t, outward_normal, self.mesh.material);
+
+        if let Some(normals) = &self.mesh.normals {
+            let shading_normal = normals[i0] * b0 + normals[i1] * b1 + normals[i2] * b2;
+            rec.set_shading_normal(shading_normal.unit_vector());
+        }
+
+        match &self.mesh.uvs {
+            Some(uvs) => rec.set_uv(
+                uvs[i0].0 * b0 + uvs[i1].0 * b1 + uvs[i2].0 * b2,
+                uvs[i0].1 * b0 + uvs[i1].1 * b1 + uvs[i2].1 * b2,
+            ),
+            None => rec.set_uv(b1, b2),
+        }
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&self.vertices()))
+    }
+
+    fn pdf_value(&self, origin: Vec3<f32>, direction: Vec3<f32>) -> f32 {
+        let [v0, v1, v2] = self.vertices();
+        triangle::pdf_value(origin, direction, v0, v1, v2)
+    }
+
+    fn random_direction(&self, origin: Vec3<f32>, _u: f32, uv: (f32, f32)) -> Vec3<f32> {
+        let [v0, v1, v2] = self.vertices();
+        triangle::random_direction(origin, v0, v1, v2, uv)
+    }
+}
@@ -1,5 +1,9 @@
-use rand::Rng;
-use std::f32::consts;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::{
+    env,
+    f32::consts,
+    io::{self, IsTerminal},
+};
 
 pub const INFTY: f32 = f32::MAX;
 pub const PI: f32 = consts::PI;
@@ -11,26 +15,78 @@ pub fn deg_to_rad(deg: f32) -> f32 {
 
 /// Hash function for fast generation of pseudo-random numbers.
 fn pcg_hash(input: u32) -> u32 {
-    let state = input * 747796405 + 2891336453;
-    let word = ((state >> ((state >> 28) + 4)) ^ state) * 277803737;
-    return (word >> 22) ^ word;
+    let state = input.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
 }
 
 pub fn rand_float(mut seed: u32) -> f32 {
     seed = pcg_hash(seed);
-    return seed as f32 / u32::MAX as f32;
+    seed as f32 / u32::MAX as f32
 }
 
-// Random f32 in the open interval `[0.0, 1.0)`
-pub fn rand() -> f32 {
-    rand::thread_rng().gen_range(0.0..1.0)
+/// Value in `[0, 1)` with the bits of `bits` as its binary fraction.
+pub fn unit_float(bits: u32) -> f32 {
+    // The 24 high bits fill the mantissa exactly, so the result never rounds up to 1
+    (bits >> 8) as f32 / (1 << 24) as f32
 }
 
-// Random f32 in the open interval `[min, max)`
-pub fn rand_on(min: f32, max: f32) -> f32 {
-    rand::thread_rng().gen_range(min..max)
+/// PCG32 pseudo-random number generator.
+///
+/// Random numbers are never drawn from a global or per-thread generator.
+/// Instead, every pixel sample gets a generator of its own, seeded from the
+/// render seed and the coordinates of the sample, so that the image only
+/// depends on the seed, whatever the number of threads.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+    inc: u64,
 }
 
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self::with_stream(seed, 0)
+    }
+
+    /// Generator for the sample with index `sample` of the pixel `(x, y)`.
+    pub fn for_sample(seed: u32, x: u32, y: u32, sample: u32) -> Self {
+        let pixel = pcg_hash(seed ^ pcg_hash(x ^ pcg_hash(y)));
+        let state = ((pixel as u64) << 32) | pcg_hash(pixel ^ sample) as u64;
+        Self::with_stream(state, sample as u64)
+    }
+
+    fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(6364136223846793005).wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    // Random f32 in the open interval `[0.0, 1.0)`
+    pub fn rand(&mut self) -> f32 {
+        unit_float(self.next_u32())
+    }
+
+    // Random f32 in the open interval `[min, max)`
+    pub fn rand_on(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.rand()
+    }
+}
+
+/// Severity of a log message, from the most to the least severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LogLevel {
     Fatal,
     Error,
@@ -39,12 +95,74 @@ pub enum LogLevel {
     Debug,
 }
 
+impl From<LogLevel> for Level {
+    /// The `log` facade has no fatal level, so fatal messages become errors.
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Fatal | LogLevel::Error => Level::Error,
+            LogLevel::Warning => Level::Warn,
+            LogLevel::Info => Level::Info,
+            LogLevel::Debug => Level::Debug,
+        }
+    }
+}
+
+/// Log `msg` through the `log` facade under the `radiant` target, leaving the
+/// filtering and printing to the logger installed by the application.
 pub fn log(level: LogLevel, msg: &str) {
-    match level {
-        LogLevel::Fatal => eprintln!("\x1b[1;41m[FATAL]\x1b[0m: {}", msg),
-        LogLevel::Error => eprintln!("\x1b[1;31m[ERROR]\x1b[0m: {}", msg),
-        LogLevel::Warning => eprintln!("\x1b[1;33m[WARNING]\x1b[0m: {}", msg),
-        LogLevel::Info => eprintln!("\x1b[1;32m[INFO]\x1b[0m: {}", msg),
-        LogLevel::Debug => eprintln!("\x1b[1;34m[DEBUG]\x1b[0m: {}", msg),
+    ::log::log!(target: "radiant", level.into(), "{}", msg);
+}
+
+/// Logger printing every message to the standard error, with ANSI colored
+/// labels when it is a terminal and `NO_COLOR` is not set.
+pub struct StderrLogger {
+    color: bool,
+}
+
+impl StderrLogger {
+    pub fn new() -> Self {
+        Self {
+            color: io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none(),
+        }
     }
+
+    /// Install the logger for the whole process, printing the messages at
+    /// least as severe as `level`.
+    pub fn init(self, level: LevelFilter) -> Result<(), SetLoggerError> {
+        ::log::set_boxed_logger(Box::new(self))?;
+        ::log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl Default for StderrLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= ::log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let (label, style) = match record.level() {
+            Level::Error => ("ERROR", "1;31"),
+            Level::Warn => ("WARNING", "1;33"),
+            Level::Info => ("INFO", "1;32"),
+            Level::Debug => ("DEBUG", "1;34"),
+            Level::Trace => ("TRACE", "1;35"),
+        };
+        if self.color {
+            eprintln!("\x1b[{}m[{}]\x1b[0m: {}", style, label, record.args());
+        } else {
+            eprintln!("[{}]: {}", label, record.args());
+        }
+    }
+
+    fn flush(&self) {}
 }
@@ -0,0 +1,456 @@
+use crate::{
+    color::{Color, RGB},
+    material::Material,
+    mesh::TriangleMesh,
+    vec3::Vec3,
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    str::{FromStr, SplitWhitespace},
+};
+
+/// Load the triangle meshes of a Wavefront OBJ file, one for each material
+/// used by its faces.
+///
+/// Polygonal faces are triangulated as fans. Faces using no material, or
+/// declared before any `usemtl` statement, get the `default_material`. The
+/// materials of the MTL libraries referenced by `mtllib` are mapped onto the
+/// materials of the renderer as follows:
+///
+/// * Transparent materials, with a dissolve `d` below one or an illumination
+///   model of `4`, `6`, `7` or `9`, become dielectrics with the index of
+///   refraction `Ni` (defaulting to `1.5`).
+/// * Materials whose specular color `Ks` is brighter than their diffuse color
+///   `Kd` become metals of color `Ks`, with a fuzz decreasing as the specular
+///   exponent `Ns` approaches `1000`.
+/// * Every other material is lambertian with albedo `Kd`.
+pub fn load_obj(path: &Path, default_material: Material) -> Result<Vec<TriangleMesh>, ObjError> {
+    parse_obj(&read(path)?, path, default_material)
+}
+
+/// Parse the contents `source` of the OBJ file at `path`, which locates its
+/// material libraries and is reported in the errors.
+fn parse_obj(
+    source: &str,
+    path: &Path,
+    default_material: Material,
+) -> Result<Vec<TriangleMesh>, ObjError> {
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut positions: Vec<Vec3<f32>> = Vec::new();
+    let mut normals: Vec<Vec3<f32>> = Vec::new();
+    let mut uvs: Vec<(f32, f32)> = Vec::new();
+    let mut materials: HashMap<String, Material> = HashMap::new();
+
+    // Faces grouped by material name, in order of first use
+    let mut groups: Vec<(Option<String>, MeshBuilder)> = vec![(None, MeshBuilder::default())];
+    let mut current_group = 0;
+
+    for (line_index, line) in source.lines().enumerate() {
+        let mut parser = LineParser::new(path, line_index + 1, line);
+        let keyword = match parser.keyword() {
+            Some(k) => k,
+            None => continue,
+        };
+
+        match keyword {
+            "v" => positions.push(parser.vec3()?),
+            "vn" => normals.push(parser.vec3()?),
+            "vt" => {
+                let u = parser.number()?;
+                let v = parser.optional_number()?.unwrap_or(0.0);
+                uvs.push((u, v));
+            }
+            "f" => {
+                let mut corners = Vec::new();
+                for vertex in parser.remaining() {
+                    corners.push(parser.face_vertex(
+                        vertex,
+                        positions.len(),
+                        uvs.len(),
+                        normals.len(),
+                    )?);
+                }
+                if corners.len() < 3 {
+                    return Err(parser.error(format!(
+                        "a face needs at least three vertices, found {}",
+                        corners.len()
+                    )));
+                }
+                let builder = &mut groups[current_group].1;
+                for i in 1..corners.len() - 1 {
+                    builder.add_triangle([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "usemtl" => {
+                let name = parser.name()?;
+                if !materials.contains_key(&name) {
+                    return Err(parser.error(format!("undefined material `{}`", name)));
+                }
+                current_group = match groups.iter().position(|(n, _)| n.as_ref() == Some(&name)) {
+                    Some(index) => index,
+                    None => {
+                        groups.push((Some(name), MeshBuilder::default()));
+                        groups.len() - 1
+                    }
+                };
+            }
+            "mtllib" => {
+                for library in parser.remaining() {
+                    materials.extend(load_mtl(&base_dir.join(library))?);
+                }
+            }
+            // Object and group names, smoothing groups, lines and points carry
+            // nothing the renderer can use
+            _ => {}
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .filter(|(_, builder)| !builder.indices.is_empty())
+        .map(|(name, builder)| {
+            let material = name.map_or(default_material, |n| materials[&n]);
+            builder.build(&positions, &uvs, &normals, material)
+        })
+        .collect())
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "unable to read {}: {}", path.display(), error),
+            Self::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|e| ObjError::Io {
+        path: path.to_path_buf(),
+        error: e,
+    })
+}
+
+/// Parameters of an MTL material relevant to the renderer.
+struct MtlMaterial {
+    diffuse: Color,
+    specular: Color,
+    shininess: f32,
+    index_refraction: Option<f32>,
+    dissolve: f32,
+    illumination: u32,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::default(),
+            shininess: 0.0,
+            index_refraction: None,
+            dissolve: 1.0,
+            illumination: 2,
+        }
+    }
+}
+
+impl From<&MtlMaterial> for Material {
+    fn from(mtl: &MtlMaterial) -> Self {
+        let brightness = |c: Color| c[RGB::Red] + c[RGB::Green] + c[RGB::Blue];
+        if mtl.dissolve < 1.0 || matches!(mtl.illumination, 4 | 6 | 7 | 9) {
+            Material::dielectric(mtl.index_refraction.unwrap_or(1.5))
+        } else if brightness(mtl.specular) > brightness(mtl.diffuse) {
+            let fuzz = (1.0 - mtl.shininess / 1000.0).clamp(0.0, 1.0);
+            Material::metal(mtl.specular, fuzz)
+        } else {
+            Material::lambertian(mtl.diffuse)
+        }
+    }
+}
+
+fn load_mtl(path: &Path) -> Result<HashMap<String, Material>, ObjError> {
+    let source = read(path)?;
+    let mut parsed: Vec<(String, MtlMaterial)> = Vec::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let mut parser = LineParser::new(path, line_index + 1, line);
+        let keyword = match parser.keyword() {
+            Some(k) => k,
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            parsed.push((parser.name()?, MtlMaterial::default()));
+            continue;
+        }
+        let mtl = match parsed.last_mut() {
+            Some((_, mtl)) => mtl,
+            None => continue,
+        };
+        match keyword {
+            "Kd" => mtl.diffuse = parser.color()?,
+            "Ks" => mtl.specular = parser.color()?,
+            "Ns" => mtl.shininess = parser.number()?,
+            "Ni" => mtl.index_refraction = Some(parser.number()?),
+            "d" => mtl.dissolve = parser.number()?,
+            "Tr" => mtl.dissolve = 1.0 - parser.number::<f32>()?,
+            "illum" => mtl.illumination = parser.number()?,
+            _ => {}
+        }
+    }
+
+    Ok(parsed
+        .iter()
+        .map(|(name, mtl)| (name.clone(), Material::from(mtl)))
+        .collect())
+}
+
+/// Corner of a face, given by zero-based indices into the position, texture
+/// coordinate and normal arrays.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// Collects the triangles of a single mesh, creating a mesh vertex for every
+/// distinct combination of position, texture coordinate and normal.
+#[derive(Default)]
+struct MeshBuilder {
+    vertices: Vec<FaceVertex>,
+    lookup: HashMap<FaceVertex, u32>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn add_triangle(&mut self, corners: [FaceVertex; 3]) {
+        for corner in corners.iter() {
+            let vertices = &mut self.vertices;
+            let index = *self.lookup.entry(*corner).or_insert_with(|| {
+                vertices.push(*corner);
+                (vertices.len() - 1) as u32
+            });
+            self.indices.push(index);
+        }
+    }
+
+    fn build(
+        self,
+        positions: &[Vec3<f32>],
+        uvs: &[(f32, f32)],
+        normals: &[Vec3<f32>],
+        material: Material,
+    ) -> TriangleMesh {
+        let mesh_positions = self
+            .vertices
+            .iter()
+            .map(|v| positions[v.position])
+            .collect();
+        let mut mesh = TriangleMesh::new(mesh_positions, self.indices, material);
+
+        // Attributes missing from some of the vertices are dropped altogether
+        let mesh_normals: Option<Vec<_>> = self
+            .vertices
+            .iter()
+            .map(|v| v.normal.map(|n| normals[n]))
+            .collect();
+        if let Some(n) = mesh_normals {
+            mesh = mesh.with_normals(n);
+        }
+        let mesh_uvs: Option<Vec<_>> = self.vertices.iter().map(|v| v.uv.map(|t| uvs[t])).collect();
+        if let Some(t) = mesh_uvs {
+            mesh = mesh.with_uvs(t);
+        }
+        mesh
+    }
+}
+
+/// Tokenizer for a single line of an OBJ or MTL file, producing errors that
+/// point at the line.
+struct LineParser<'a> {
+    path: &'a Path,
+    line: usize,
+    tokens: SplitWhitespace<'a>,
+}
+
+impl<'a> LineParser<'a> {
+    fn new(path: &'a Path, line: usize, source: &'a str) -> Self {
+        let content = source.split('#').next().unwrap_or("");
+        Self {
+            path,
+            line,
+            tokens: content.split_whitespace(),
+        }
+    }
+
+    fn error(&self, message: String) -> ObjError {
+        ObjError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message,
+        }
+    }
+
+    fn keyword(&mut self) -> Option<&'a str> {
+        self.tokens.next()
+    }
+
+    fn remaining(&mut self) -> Vec<&'a str> {
+        self.tokens.by_ref().collect()
+    }
+
+    fn name(&mut self) -> Result<String, ObjError> {
+        let name = self.remaining().join(" ");
+        if name.is_empty() {
+            return Err(self.error("missing name".to_string()));
+        }
+        Ok(name)
+    }
+
+    fn optional_number<T: FromStr>(&mut self) -> Result<Option<T>, ObjError> {
+        match self.tokens.next() {
+            Some(token) => token
+                .parse()
+                .map(Some)
+                .map_err(|_| self.error(format!("invalid number `{}`", token))),
+            None => Ok(None),
+        }
+    }
+
+    fn number<T: FromStr>(&mut self) -> Result<T, ObjError> {
+        self.optional_number()?
+            .ok_or_else(|| self.error("missing number".to_string()))
+    }
+
+    fn vec3(&mut self) -> Result<Vec3<f32>, ObjError> {
+        Ok(Vec3::new(self.number()?, self.number()?, self.number()?))
+    }
+
+    fn color(&mut self) -> Result<Color, ObjError> {
+        Ok(Color::new(self.number()?, self.number()?, self.number()?))
+    }
+
+    /// Parse a face corner of the form `v`, `v/vt`, `v//vn` or `v/vt/vn`,
+    /// where negative indices count backwards from the last element defined.
+    fn face_vertex(
+        &self,
+        token: &str,
+        positions: usize,
+        uvs: usize,
+        normals: usize,
+    ) -> Result<FaceVertex, ObjError> {
+        let mut parts = token.split('/');
+        let position = match parts.next() {
+            Some(p) if !p.is_empty() => self.face_index(p, positions, "vertex")?,
+            _ => return Err(self.error(format!("malformed face vertex `{}`", token))),
+        };
+        let uv = match parts.next() {
+            Some(t) if !t.is_empty() => Some(self.face_index(t, uvs, "texture coordinate")?),
+            _ => None,
+        };
+        let normal = match parts.next() {
+            Some(n) if !n.is_empty() => Some(self.face_index(n, normals, "normal")?),
+            _ => None,
+        };
+        if parts.next().is_some() {
+            return Err(self.error(format!("malformed face vertex `{}`", token)));
+        }
+        Ok(FaceVertex {
+            position,
+            uv,
+            normal,
+        })
+    }
+
+    fn face_index(&self, token: &str, defined: usize, what: &str) -> Result<usize, ObjError> {
+        let index: i64 = token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} index `{}`", what, token)))?;
+        let resolved = if index < 0 {
+            defined as i64 + index
+        } else {
+            index - 1
+        };
+        if index == 0 || resolved < 0 || resolved >= defined as i64 {
+            return Err(self.error(format!(
+                "face refers to {} {}, but only {} are defined",
+                what, index, defined
+            )));
+        }
+        Ok(resolved as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(source: &str) -> (usize, String) {
+        match parse_obj(source, Path::new("test.obj"), Material::default()) {
+            Err(ObjError::Parse { line, message, .. }) => (line, message),
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("parsing succeeded"),
+        }
+    }
+
+    #[test]
+    fn malformed_face_vertex_is_reported_at_its_line() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 /3\n";
+        assert_eq!(
+            parse_error(source),
+            (5, "malformed face vertex `/3`".to_string())
+        );
+    }
+
+    #[test]
+    fn out_of_range_index_is_reported_at_its_line() {
+        let source = "v 0 0 0\nv 1 0 0\nf 1 2 3\n";
+        assert_eq!(
+            parse_error(source),
+            (
+                3,
+                "face refers to vertex 3, but only 2 are defined".to_string()
+            )
+        );
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 1 2\n";
+        assert_eq!(
+            parse_error(source),
+            (
+                4,
+                "face refers to vertex -4, but only 3 are defined".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn polygons_are_triangulated() {
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n";
+        let mut meshes = parse_obj(source, Path::new("test.obj"), Material::default()).unwrap();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes.remove(0).into_triangles().len(), 2);
+    }
+}
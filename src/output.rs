@@ -0,0 +1,293 @@
+use crate::{aov::Aov, color::Color, film::Film, tonemap::ToneMap};
+use exr::prelude::{
+    f16, AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, SmallVec,
+    WritableImage,
+};
+use image::{codecs::hdr::HdrEncoder, ImageError, Rgb, RgbImage};
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// File format of the rendered image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// 8-bit image, gamma corrected and clamped.
+    #[default]
+    Png,
+    /// OpenEXR image with linear half or single precision float channels.
+    Exr,
+    /// Portable float map, with linear single precision RGB pixels.
+    Pfm,
+    /// Radiance RGBE image, with a shared exponent per pixel.
+    Hdr,
+}
+
+impl OutputFormat {
+    /// Format matching the extension of `path`, if any.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Exr => "exr",
+            Self::Pfm => "pfm",
+            Self::Hdr => "hdr",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(Self::Png),
+            "exr" => Ok(Self::Exr),
+            "pfm" => Ok(Self::Pfm),
+            "hdr" => Ok(Self::Hdr),
+            _ => Err(format!(
+                "unknown output format `{}`, expected `png`, `exr`, `pfm` or `hdr`",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// Destination of a render.
+///
+/// Float formats hold the linear radiance of the film, and are written
+/// together with an 8-bit PNG preview of the same name.
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub path: PathBuf,
+    pub format: OutputFormat,
+    /// Store the channels of OpenEXR files as half rather than single
+    /// precision floats.
+    pub half: bool,
+    /// Tone mapping of the PNG image. Float formats keep the linear radiance.
+    pub tone_map: ToneMap,
+    /// Output variables rendered and written along with the radiance.
+    pub aovs: Vec<Aov>,
+}
+
+impl Output {
+    /// Output to `path`, in the format given by its extension or else as a
+    /// PNG image.
+    pub fn new(path: PathBuf) -> Self {
+        let format = OutputFormat::from_path(&path).unwrap_or_default();
+        Self {
+            path,
+            format,
+            half: false,
+            tone_map: ToneMap::default(),
+            aovs: Vec::new(),
+        }
+    }
+
+    /// Path of the PNG preview written next to float images.
+    pub fn preview_path(&self) -> PathBuf {
+        self.path.with_extension(OutputFormat::Png.extension())
+    }
+
+    /// Path of an extra image written next to the output, named after it with
+    /// the given suffix.
+    pub fn sibling_path(&self, suffix: &str, format: OutputFormat) -> PathBuf {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        self.path
+            .with_file_name(format!("{}_{}.{}", stem, suffix, format.extension()))
+    }
+
+    /// Write the film, and its PNG preview for float formats.
+    ///
+    /// OpenEXR files hold the output variables as extra channels, while the
+    /// other formats write each of them to a sibling image.
+    pub fn write(&self, film: &Film) -> Result<(), OutputError> {
+        let pixels = || {
+            top_down_pixels(film)
+                .map(|c| c.channels())
+                .collect::<Vec<_>>()
+        };
+        let size = (film.width(), film.height());
+        match self.format {
+            OutputFormat::Png => write_png(film, &self.path, &self.tone_map)?,
+            OutputFormat::Exr => write_exr(film, &self.aovs, &self.path, self.half)?,
+            OutputFormat::Pfm => write_pfm(&pixels(), size, &self.path)?,
+            OutputFormat::Hdr => write_hdr(&pixels(), size, &self.path)?,
+        }
+        for &aov in &self.aovs {
+            let path = self.sibling_path(aov.name(), self.format);
+            match self.format {
+                OutputFormat::Png => save_png(aov.to_rgb_image(film, &self.tone_map), &path)?,
+                OutputFormat::Exr => {}
+                OutputFormat::Pfm => write_pfm(&aov.rgb_pixels(film), size, &path)?,
+                OutputFormat::Hdr => write_hdr(&aov.rgb_pixels(film), size, &path)?,
+            }
+        }
+        if self.format != OutputFormat::Png {
+            write_png(film, &self.preview_path(), &self.tone_map)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum OutputError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Image {
+        path: PathBuf,
+        error: ImageError,
+    },
+    Exr {
+        path: PathBuf,
+        error: exr::error::Error,
+    },
+}
+
+impl Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => {
+                write!(f, "unable to write image {}: {}", path.display(), error)
+            }
+            Self::Image { path, error } => {
+                write!(f, "unable to write image {}: {}", path.display(), error)
+            }
+            Self::Exr { path, error } => {
+                write!(f, "unable to write image {}: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OutputError {}
+
+/// Linear radiance of the film with the rows in the usual top to bottom order.
+fn top_down_pixels(film: &Film) -> impl Iterator<Item = Color> + '_ {
+    (0..film.height())
+        .rev()
+        .flat_map(move |y| (0..film.width()).map(move |x| film.pixel(x, y).color()))
+}
+
+pub fn write_png(film: &Film, path: &Path, tone_map: &ToneMap) -> Result<(), OutputError> {
+    save_png(film.to_rgb_image(tone_map), path)
+}
+
+fn save_png(image: RgbImage, path: &Path) -> Result<(), OutputError> {
+    image.save(path).map_err(|error| OutputError::Image {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+/// Write a single layer OpenEXR image with one channel per color component,
+/// followed by the channels of `aovs` named `<aov>.<channel>`.
+pub fn write_exr(film: &Film, aovs: &[Aov], path: &Path, half: bool) -> Result<(), OutputError> {
+    let mut channels: [Vec<f32>; 3] = Default::default();
+    for color in top_down_pixels(film) {
+        for (channel, value) in channels.iter_mut().zip(color.channels()) {
+            channel.push(value);
+        }
+    }
+    let samples = |values: Vec<f32>| {
+        if half {
+            FlatSamples::F16(values.into_iter().map(f16::from_f32).collect())
+        } else {
+            FlatSamples::F32(values)
+        }
+    };
+    let [r, g, b] = channels;
+    let mut list = vec![
+        AnyChannel::new("R", samples(r)),
+        AnyChannel::new("G", samples(g)),
+        AnyChannel::new("B", samples(b)),
+    ];
+    for &aov in aovs {
+        if aov.is_id() {
+            // Identifiers stay exact as unsigned integers
+            let name = format!("{}.{}", aov, aov.channel_names()[0]);
+            list.push(AnyChannel::new(
+                name.as_str(),
+                FlatSamples::U32(aov.ids(film)),
+            ));
+            continue;
+        }
+        for (name, values) in aov.channel_names().iter().zip(aov.channels(film)) {
+            list.push(AnyChannel::new(
+                format!("{}.{}", aov, name).as_str(),
+                samples(values),
+            ));
+        }
+    }
+    let layer = Layer::new(
+        (film.width(), film.height()),
+        LayerAttributes::default(),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(SmallVec::from_vec(list)),
+    );
+    Image::from_layer(layer)
+        .write()
+        .to_file(path)
+        .map_err(|error| OutputError::Exr {
+            path: path.to_path_buf(),
+            error,
+        })
+}
+
+/// Write `pixels`, given with the rows from the top of the image to the
+/// bottom, as a little-endian portable float map. Its rows go from the bottom
+/// of the image to the top.
+pub fn write_pfm(
+    pixels: &[[f32; 3]],
+    (width, height): (usize, usize),
+    path: &Path,
+) -> Result<(), OutputError> {
+    let write = || -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "PF\n{} {}\n-1.0\n", width, height)?;
+        for row in pixels.chunks(width).rev() {
+            for value in row.iter().flatten() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        writer.flush()
+    };
+    write().map_err(|error| OutputError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+/// Write `pixels`, given with the rows from the top of the image to the
+/// bottom, as a Radiance RGBE image.
+pub fn write_hdr(
+    pixels: &[[f32; 3]],
+    (width, height): (usize, usize),
+    path: &Path,
+) -> Result<(), OutputError> {
+    let pixels: Vec<Rgb<f32>> = pixels.iter().map(|&p| Rgb(p)).collect();
+    let file = File::create(path).map_err(|error| OutputError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    HdrEncoder::new(BufWriter::new(file))
+        .encode(&pixels, width, height)
+        .map_err(|error| OutputError::Image {
+            path: path.to_path_buf(),
+            error,
+        })
+}
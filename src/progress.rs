@@ -0,0 +1,170 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Progress of a render, reported after every row.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    /// Index of the current pass over the image. Adaptive sampling makes
+    /// several passes, each over every row.
+    pub pass: u32,
+    /// Number of rows of the current pass already rendered.
+    pub rows_done: usize,
+    /// Number of rows of the image.
+    pub rows: usize,
+    /// Number of samples taken so far, over every pass.
+    pub samples: u64,
+    /// Number of samples the render takes at most.
+    pub samples_budget: u64,
+    /// Time spent rendering so far.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Estimated time left before the sample budget is spent, once some
+    /// samples were taken. Adaptive sampling may finish sooner.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.samples == 0 {
+            return None;
+        }
+        let left = self.samples_budget.saturating_sub(self.samples);
+        Some(self.elapsed.mul_f64(left as f64 / self.samples as f64))
+    }
+}
+
+/// Receiver of the progress of a render.
+///
+/// Rows are rendered in parallel, so the methods are called from the worker
+/// threads and must return quickly.
+pub trait ProgressSink: Send + Sync {
+    /// Called at the start of every pass, with no rows done, and after every
+    /// row.
+    fn update(&self, progress: &Progress);
+
+    /// Describe what the render is doing, such as the current adaptive round.
+    fn message(&self, _message: &str) {}
+
+    /// Called once the render is over, whether it finished or was cancelled.
+    fn finish(&self) {}
+}
+
+/// Progress bar drawn on the terminal.
+pub struct IndicatifProgress {
+    bar: ProgressBar,
+}
+
+impl IndicatifProgress {
+    pub fn new() -> Self {
+        let style = ProgressStyle::default_bar()
+            .template(concat!(
+                "{spinner:.green} [{pos:>3}/{len:3}] ",
+                "{bar:40.magenta/blue} [time: {elapsed_precise}]\n{msg}"
+            ))
+            .progress_chars("=>-")
+            .tick_chars("|/-|/-\\");
+        let bar = ProgressBar::new(0);
+        bar.set_style(style);
+        Self { bar }
+    }
+}
+
+impl Default for IndicatifProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressSink for IndicatifProgress {
+    fn update(&self, progress: &Progress) {
+        if progress.rows_done == 0 {
+            self.bar.reset();
+            self.bar.set_length(progress.rows as u64);
+        }
+        self.bar.set_position(progress.rows_done as u64);
+    }
+
+    fn message(&self, message: &str) {
+        self.bar.set_message(message.to_string());
+    }
+
+    fn finish(&self) {
+        self.bar.finish();
+    }
+}
+
+/// Sink ignoring the progress.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SilentProgress;
+
+impl ProgressSink for SilentProgress {
+    fn update(&self, _progress: &Progress) {}
+}
+
+/// Sink handing the progress over to a function, and optionally the end of
+/// the render to another one.
+pub struct CallbackProgress<F, G = fn()> {
+    callback: F,
+    on_finish: Option<G>,
+}
+
+impl<F: Fn(&Progress) + Send + Sync> CallbackProgress<F> {
+    pub fn new(callback: F) -> Self {
+        Self {
+            callback,
+            on_finish: None,
+        }
+    }
+}
+
+impl<F: Fn(&Progress) + Send + Sync, G: Fn() + Send + Sync> CallbackProgress<F, G> {
+    /// Call `on_finish` once the render is over.
+    pub fn on_finish<H: Fn() + Send + Sync>(self, on_finish: H) -> CallbackProgress<F, H> {
+        CallbackProgress {
+            callback: self.callback,
+            on_finish: Some(on_finish),
+        }
+    }
+}
+
+impl<F: Fn(&Progress) + Send + Sync, G: Fn() + Send + Sync> ProgressSink
+    for CallbackProgress<F, G>
+{
+    fn update(&self, progress: &Progress) {
+        (self.callback)(progress);
+    }
+
+    fn finish(&self) {
+        if let Some(on_finish) = &self.on_finish {
+            on_finish();
+        }
+    }
+}
+
+/// Flag asking a render to stop early, shared between the render and the code
+/// that may cancel it.
+///
+/// The render checks it before every pixel, and then returns the film with
+/// the samples taken until then.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
@@ -1,6 +1,6 @@
 use crate::vec3::Vec3;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Ray {
     origin: Vec3<f32>,
     dir: Vec3<f32>,
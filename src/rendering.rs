@@ -1,79 +1,408 @@
 use crate::{
-    camera::{self, Camera},
+    aov::AovValues,
+    background::Background,
+    bvh::Bvh,
     color::Color,
+    denoise::Denoiser,
+    error::Result,
+    film::{Film, FilmPixel, SplatBuffer},
     misc::{self, LogLevel},
+    output::{Output, OutputError, OutputFormat},
+    progress::{CancellationToken, Progress, ProgressSink},
     ray::Ray,
-    scene::{self, SceneType},
-    surf::HitRecord,
+    sampler::Sampler,
+    scene::Scene,
+    settings::RenderSettings,
+    surf::{HitRecord, Surface},
     surf_list::SurfList,
 };
-use image::{ImageBuffer, Rgb};
-use indicatif::{ProgressBar, ProgressStyle};
-use rayon::{
-    iter::{IndexedParallelIterator, ParallelIterator},
-    slice::ParallelSliceMut,
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Instant,
 };
-use std::path::Path;
 
-fn ray_color(ray: Ray, world: &SurfList, depth: i32) -> Color {
-    if depth <= 0 {
-        // Exceeded maximum number of bounces, considers that the location is
-        // near a shadow, so it returns a black pixel
-        Color::new(0.0, 0.0, 0.0)
+/// Weight of a sample drawn with density `pdf` when the same path could also
+/// have been drawn with density `other_pdf`, following the power heuristic.
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 {
+        a / (a + b)
     } else {
+        0.0
+    }
+}
+
+/// Smallest ray parameter at which hits are accepted. Rounding errors in the
+/// hit points grow with their distance from the origin of the world, so
+/// rays leaving a surface must skip a proportional distance to avoid hitting
+/// it again.
+fn min_parameter(ray: &Ray) -> f32 {
+    let o = ray.origin();
+    let scale = 1.0 + o.x().abs().max(o.y().abs()).max(o.z().abs());
+    0.001 * scale / ray.direction().len()
+}
+
+/// Light reaching `rec.point()` directly from a point picked on the `lights`
+/// with the sample values `u` and `uv`, weighted against finding the same
+/// light by sampling the material of the hit. The result is still to be
+/// multiplied by the attenuation of the material.
+fn sample_lights(
+    ray: &Ray,
+    rec: &HitRecord,
+    world: &dyn Surface,
+    lights: &SurfList,
+    u: f32,
+    uv: (f32, f32),
+) -> Color {
+    let light_ray = Ray::new(rec.point(), lights.random_direction(rec.point(), u, uv));
+    let light_pdf = lights.pdf_value(light_ray.origin(), light_ray.direction());
+    let pdf = rec
+        .material()
+        .scattering_pdf(ray, rec, &light_ray)
+        .unwrap_or(0.0);
+    if light_pdf <= 0.0 || pdf <= 0.0 {
+        return Color::default();
+    }
+
+    let mut light_rec = HitRecord::new();
+    if world.hit(
+        &light_ray,
+        min_parameter(&light_ray),
+        misc::INFTY,
+        &mut light_rec,
+    ) {
+        let radiance = light_rec.material().emitted(&light_rec);
+        radiance * (pdf * power_heuristic(light_pdf, pdf) / light_pdf)
+    } else {
+        Color::default()
+    }
+}
+
+/// Light arriving along the camera `ray`, estimated by following a single
+/// path through the scene.
+///
+/// Paths longer than `rr_depth` bounces survive each further bounce with a
+/// probability that follows their throughput, and the survivors are weighted
+/// up to keep the estimate unbiased. Only the paths that reach `max_depth`
+/// bounces are cut short.
+///
+/// Every bounce draws the same dimensions from the `sampler`, in the same
+/// order, whether it uses them or not.
+fn trace_path(
+    mut ray: Ray,
+    world: &dyn Surface,
+    lights: &SurfList,
+    background: &Background,
+    settings: &RenderSettings,
+    sampler: &mut dyn Sampler,
+) -> (Color, AovValues) {
+    let mut aov = AovValues::default();
+    // Light found at the first hit, and through any later bounce
+    let mut direct = Color::default();
+    let mut indirect = Color::default();
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    // Density with which the last bounce scattered into `ray`, or `None` for
+    // the camera ray and specular bounces, whose light cannot be found by
+    // light sampling
+    let mut bsdf_pdf: Option<f32> = None;
+
+    for depth in 0..settings.max_depth {
         let mut rec = HitRecord::new();
-        if world.hit(&ray, 0.001, misc::INFTY, &mut rec) {
-            match rec.material().scatter(ray, rec) {
-                Some((scattered, attenuation)) => {
-                    attenuation * ray_color(scattered, world, depth - 1)
-                }
-                None => Color::new(0.0, 0.0, 0.0),
-            }
+        let light = if depth <= 1 {
+            &mut direct
         } else {
-            let unit_dir = ray.direction().unit();
-            let t = 0.5 * (unit_dir.y() + 1.0);
-            Color::new(1.0, 1.0, 1.0) * (1.0 - t) as f32 + Color::new(0.5, 0.7, 1.0) * t as f32
+            &mut indirect
+        };
+        if !world.hit(&ray, min_parameter(&ray), misc::INFTY, &mut rec) {
+            let radiance = background.radiance(ray.direction());
+            if depth == 0 {
+                aov.emission = radiance;
+            }
+            *light += throughput * radiance;
+            break;
+        }
+        if depth == 0 {
+            aov.albedo = rec.material().albedo();
+            aov.normal = rec.normal();
+            aov.depth = rec.parameter();
+            aov.position = rec.point();
+        }
+
+        let u_scatter = sampler.get_1d();
+        let uv_scatter = sampler.get_2d();
+        let u_light = sampler.get_1d();
+        let uv_light = sampler.get_2d();
+        let u_roulette = sampler.get_1d();
+
+        let material = rec.material();
+        let mut emitted = material.emitted(&rec);
+        if let Some(pdf) = bsdf_pdf {
+            // Light sampling at the previous bounce may have found this emitter too
+            let light_pdf = lights.pdf_value(ray.origin(), ray.direction());
+            emitted *= power_heuristic(pdf, light_pdf);
+        }
+        if depth == 0 {
+            aov.emission = emitted;
+        }
+        *light += throughput * emitted;
+
+        let (scattered, attenuation) = match material.scatter(ray, rec, u_scatter, uv_scatter) {
+            Some(scatter) => scatter,
+            None => break,
+        };
+        bsdf_pdf = material.scattering_pdf(&ray, &rec, &scattered);
+        if bsdf_pdf.is_some() && !lights.is_empty() {
+            let found = sample_lights(&ray, &rec, world, lights, u_light, uv_light);
+            let light = if depth == 0 {
+                &mut direct
+            } else {
+                &mut indirect
+            };
+            *light += throughput * attenuation * found;
+        }
+        throughput = throughput * attenuation;
+        ray = scattered;
+
+        // Russian roulette
+        if depth + 1 >= settings.rr_depth {
+            let survival = throughput.max_component().min(0.95);
+            if u_roulette >= survival {
+                break;
+            }
+            throughput /= survival;
         }
     }
+    aov.direct = direct;
+    aov.indirect = indirect;
+    (direct + indirect, aov)
 }
 
-pub fn render(output_path: &Path, camera: Camera, scene_type: SceneType) {
-    let world = scene::make_scene(scene_type);
-
-    let progress_style = ProgressStyle::default_bar()
-        .template(concat!(
-            "{spinner:.green} [{pos:>3}/{len:3}] ",
-            "{bar:40.magenta/blue} [time: {elapsed_precise}]\n{msg}"
-        ))
-        .progress_chars("=>-")
-        .tick_chars("|/-|/-\\");
-    let progress_lines = ProgressBar::new(camera::IMAGE_HEIGHT as u64);
-    progress_lines.set_style(progress_style);
-
-    let mut pixel_buffer = [Rgb::from([0u8, 0u8, 0u8]); camera::IMAGE_HEIGHT * camera::IMAGE_WIDTH];
-    pixel_buffer
-        .par_chunks_mut(camera::IMAGE_WIDTH)
-        .enumerate()
-        .for_each(|(pixel_y, row)| {
-            for (pixel_x, px) in row.iter_mut().enumerate() {
-                let mut px_col = Color::default();
-                for _ in 0..camera::SAMPLES_PER_PIXEL {
-                    let u = (pixel_x as f32 + misc::rand()) / (camera::IMAGE_WIDTH - 1) as f32;
-                    let v = (pixel_y as f32 + misc::rand()) / (camera::IMAGE_HEIGHT - 1) as f32;
-                    let r = camera.get_ray(u, v);
-                    px_col += ray_color(r, &world, camera::MAX_DEPTH);
-                }
-                *px = px_col.rgb();
-            }
-            progress_lines.inc(1);
-        });
-
-    let img = ImageBuffer::from_fn(
-        camera::IMAGE_WIDTH as u32,
-        camera::IMAGE_HEIGHT as u32,
-        |x, y| {
-            pixel_buffer[x as usize + (camera::IMAGE_HEIGHT - 1 - y as usize) * camera::IMAGE_WIDTH]
-        },
+/// Number of rows rendered in parallel before their samples are merged into
+/// the film.
+const ROWS_PER_BATCH: usize = 64;
+
+/// Render `scene` and write the image, and the output variables it asks for,
+/// to `output`. A cancelled render still writes the partial image.
+pub fn render(
+    output: &Output,
+    scene: Scene,
+    settings: &RenderSettings,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let film = render_film(scene, settings, !output.aovs.is_empty(), progress, cancel)?;
+    output.write(&film)?;
+    if settings.spp_heatmap {
+        let path = output.sibling_path("spp", OutputFormat::Png);
+        film.to_sample_heatmap()
+            .save(&path)
+            .map_err(|error| OutputError::Image { path, error })?;
+    }
+    Ok(())
+}
+
+/// Render `scene` into a film held in memory, also accumulating the output
+/// variables if `with_aovs` is set. The film can then be converted into an
+/// image, or written with an `Output`.
+///
+/// The progress goes to `progress`. Once `cancel` is cancelled, the render
+/// stops after the pixels being sampled and returns the partial film, neither
+/// denoised nor with the object and material identifiers.
+pub fn render_film(
+    scene: Scene,
+    settings: &RenderSettings,
+    with_aovs: bool,
+    progress: &dyn ProgressSink,
+    cancel: &CancellationToken,
+) -> Result<Film> {
+    settings.validate()?;
+    let start_time = Instant::now();
+    let Scene {
+        world,
+        lights,
+        camera,
+        background,
+    } = scene;
+    let camera = camera.build(settings);
+    let world = Bvh::new(world);
+    misc::log(
+        LogLevel::Debug,
+        &format!("Built BVH over {} surfaces", world.len()),
     );
-    let _ = img.save(output_path);
+
+    let width = settings.image_width;
+    let height = settings.image_height;
+
+    let max_spp = match settings.noise_threshold {
+        Some(_) => settings.max_spp.max(1),
+        None => settings.samples_per_pixel,
+    };
+
+    let filter = settings.filter.build();
+    // The denoiser is guided by the albedo and normal
+    let with_aovs = with_aovs || settings.denoise;
+
+    // Samples taken over every pass, and the number of the current pass
+    let samples_taken = AtomicU64::new(0);
+    let mut pass = 0;
+    let report = |pass, rows_done| {
+        progress.update(&Progress {
+            pass,
+            rows_done,
+            rows: height,
+            samples: samples_taken.load(Ordering::Relaxed),
+            samples_budget: settings.samples_per_pixel as u64 * (width * height) as u64,
+            elapsed: start_time.elapsed(),
+        })
+    };
+
+    // Add to every pixel the number of samples given by `samples_for`,
+    // continuing its sample indices where the previous pass stopped, and
+    // return the total number of samples taken. Rows are rendered in parallel
+    // by batches, whose splats are then merged into the film in order.
+    let mut render_pass = |film: &mut Film, samples_for: &(dyn Fn(&FilmPixel) -> u32 + Sync)| {
+        let rows_done = AtomicUsize::new(0);
+        report(pass, 0);
+        let mut taken = 0;
+        for first_row in (0..height).step_by(ROWS_PER_BATCH) {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let rows = first_row..(first_row + ROWS_PER_BATCH).min(height);
+            let batch: Vec<(SplatBuffer, u64)> = film
+                .par_rows_mut(rows)
+                .map(|(pixel_y, row)| {
+                    let mut sampler = settings.sampler.build(max_spp, settings.seed);
+                    let mut splats =
+                        SplatBuffer::new((width, height), pixel_y, filter.as_ref(), with_aovs);
+                    let mut row_taken = 0;
+                    for (pixel_x, px) in row.iter_mut().enumerate() {
+                        if cancel.is_cancelled() {
+                            break;
+                        }
+                        let start = px.samples();
+                        let count = samples_for(px);
+                        sampler.start_pixel(pixel_x as u32, pixel_y as u32, start..start + count);
+                        for sample in start..start + count {
+                            sampler.start_pixel_sample(pixel_x as u32, pixel_y as u32, sample);
+                            let (jitter_x, jitter_y) = sampler.get_2d();
+                            let lens = sampler.get_2d();
+                            let u = (pixel_x as f32 + jitter_x) / (width - 1) as f32;
+                            let v = (pixel_y as f32 + jitter_y) / (height - 1) as f32;
+                            let r = camera.get_ray(u, v, lens);
+                            let (radiance, aov) = trace_path(
+                                r,
+                                &world,
+                                &lights,
+                                &background,
+                                settings,
+                                sampler.as_mut(),
+                            );
+                            px.add_sample(radiance);
+                            splats.add_sample(
+                                (pixel_x, pixel_y),
+                                (jitter_x, jitter_y),
+                                radiance,
+                                &aov,
+                                filter.as_ref(),
+                            );
+                        }
+                        row_taken += count as u64;
+                    }
+                    samples_taken.fetch_add(row_taken, Ordering::Relaxed);
+                    report(pass, rows_done.fetch_add(1, Ordering::Relaxed) + 1);
+                    (splats, row_taken)
+                })
+                .collect();
+            for (splats, row_taken) in &batch {
+                film.merge(splats);
+                taken += row_taken;
+            }
+        }
+        pass += 1;
+        taken
+    };
+
+    let mut film = Film::new(width, height);
+    if with_aovs {
+        film = film.with_aovs();
+    }
+    match settings.noise_threshold {
+        None => {
+            render_pass(&mut film, &|_| settings.samples_per_pixel);
+        }
+        Some(threshold) => {
+            // Every pixel first takes `min_spp` samples to estimate its noise.
+            // The rest of the budget then goes in rounds of at most `min_spp`
+            // samples to the pixels still above the threshold, until none are
+            // left or the budget runs out.
+            let min_spp = settings.min_spp.clamp(1, max_spp);
+            let budget = settings.samples_per_pixel as u64 * (width * height) as u64;
+            let is_noisy =
+                |px: &FilmPixel| px.samples() < max_spp && px.relative_error() > threshold;
+
+            let mut spent = render_pass(&mut film, &|_| min_spp);
+            let mut round = 1;
+            while !cancel.is_cancelled() {
+                let noisy = film.pixels().par_iter().filter(|px| is_noisy(px)).count() as u64;
+                let batch = match budget.saturating_sub(spent).checked_div(noisy) {
+                    Some(batch) if batch > 0 => batch.min(min_spp as u64) as u32,
+                    _ => break,
+                };
+                progress.message(&format!("Adaptive round {}: {} noisy pixels", round, noisy));
+                spent += render_pass(&mut film, &|px| {
+                    if is_noisy(px) {
+                        batch.min(max_spp - px.samples())
+                    } else {
+                        0
+                    }
+                });
+                round += 1;
+            }
+            misc::log(
+                LogLevel::Debug,
+                &format!(
+                    "Adaptive sampling took {:.1} samples per pixel on average over {} rounds",
+                    spent as f64 / (width * height) as f64,
+                    round
+                ),
+            );
+        }
+    }
+
+    // A cancelled render returns as soon as possible, without the identifiers
+    // nor denoising
+    let cancelled = cancel.is_cancelled();
+    if with_aovs && !cancelled {
+        // Identifiers cannot be averaged, so they are taken from a single ray
+        // through the center of each pixel
+        let ids = (0..width * height)
+            .into_par_iter()
+            .map(|i| {
+                let u = ((i % width) as f32 + 0.5) / (width - 1) as f32;
+                let v = ((i / width) as f32 + 0.5) / (height - 1) as f32;
+                let r = camera.get_ray(u, v, (0.5, 0.5));
+                let mut rec = HitRecord::new();
+                if world.hit(&r, min_parameter(&r), misc::INFTY, &mut rec) {
+                    (rec.object_id(), rec.material().id())
+                } else {
+                    (0, 0)
+                }
+            })
+            .collect();
+        film.set_ids(ids);
+    }
+
+    if settings.denoise && !cancelled {
+        Denoiser::default().denoise_film(&mut film);
+    }
+    progress.finish();
+    if cancelled {
+        misc::log(
+            LogLevel::Warning,
+            "Render cancelled, the image is incomplete",
+        );
+    }
+    Ok(film)
 }
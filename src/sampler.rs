@@ -0,0 +1,435 @@
+use crate::{
+    misc::{self, unit_float, Rng},
+    vec3::Vec3,
+};
+use std::{
+    fmt::{self, Display},
+    ops::Range,
+    str::FromStr,
+};
+
+/// Source of the sample values of a render.
+///
+/// Every pixel sample draws its values dimension by dimension: the camera
+/// takes two 2D samples for the position in the pixel and on the lens, and
+/// every bounce then takes the same fixed set of dimensions, whether it uses
+/// them or not. The values of a given dimension are thus always spent on the
+/// same decision, which is what lets the low-discrepancy samplers spread them
+/// evenly across the samples of a pixel.
+pub trait Sampler {
+    /// Announce that the samples with indices in `samples` of the pixel
+    /// `(x, y)` are taken next, together, such as in one pass of adaptive
+    /// sampling. Samplers dividing the samples of a pixel among strata spread
+    /// these ones over strata of their own.
+    fn start_pixel(&mut self, _x: u32, _y: u32, _samples: Range<u32>) {}
+
+    /// Start drawing the values of the sample with index `index` of the pixel
+    /// `(x, y)`, from the first dimension.
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32);
+
+    fn get_1d(&mut self) -> f32;
+
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+/// Kind of sampler used for a render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SamplerType {
+    Independent,
+    Stratified,
+    Halton,
+    #[default]
+    Sobol,
+}
+
+impl SamplerType {
+    /// Sampler for renders with `samples_per_pixel` samples in each pixel.
+    pub fn build(self, samples_per_pixel: u32, seed: u32) -> Box<dyn Sampler> {
+        match self {
+            Self::Independent => Box::new(IndependentSampler::new(seed)),
+            Self::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            Self::Halton => Box::new(HaltonSampler::new(seed)),
+            Self::Sobol => Box::new(SobolSampler::new(seed)),
+        }
+    }
+}
+
+impl FromStr for SamplerType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "independent" => Ok(Self::Independent),
+            "stratified" => Ok(Self::Stratified),
+            "halton" => Ok(Self::Halton),
+            "sobol" => Ok(Self::Sobol),
+            _ => Err(format!(
+                "unknown sampler `{}` [options: independent, stratified, halton, sobol]",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for SamplerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Independent => "independent",
+            Self::Stratified => "stratified",
+            Self::Halton => "halton",
+            Self::Sobol => "sobol",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Uniform random values, independent of each other.
+pub struct IndependentSampler {
+    seed: u32,
+    rng: Rng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            rng: Rng::new(seed as u64),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.rng = Rng::for_sample(self.seed, x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.rng.rand()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.rng.rand(), self.rng.rand())
+    }
+}
+
+/// Jittered samples, one in each stratum of every dimension.
+///
+/// The strata of a dimension are visited in an order given by a random
+/// permutation of the sample indices, different for every pixel and
+/// dimension, so that the dimensions are not correlated with each other.
+///
+/// The samples of a pixel are divided into `samples_per_pixel` strata, unless
+/// `start_pixel` announces a different set of samples to stratify.
+pub struct StratifiedSampler {
+    seed: u32,
+    samples: Range<u32>,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u32) -> Self {
+        Self {
+            seed,
+            samples: 0..samples_per_pixel,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn next_hash(&mut self) -> u64 {
+        // Sets of samples starting at different indices get their own
+        // permutations and jitter
+        let seed = self.seed ^ self.samples.start.wrapping_mul(0x9e3779b9);
+        let hash = hash(self.pixel.0, self.pixel.1, self.dimension, seed);
+        self.dimension += 1;
+        hash
+    }
+
+    /// Number of strata, and index of the current sample among them.
+    fn stratum_count_and_index(&self) -> (u32, u32) {
+        let n = self.samples.len().max(1) as u32;
+        (n, self.index.wrapping_sub(self.samples.start) % n)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel(&mut self, _x: u32, _y: u32, samples: Range<u32>) {
+        self.samples = samples;
+    }
+
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let hash = self.next_hash();
+        let (n, index) = self.stratum_count_and_index();
+        let stratum = permutation_element(index, n, hash as u32);
+        let jitter = unit_float((hash >> 32) as u32);
+        (stratum as f32 + jitter) / n as f32
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let hash = self.next_hash();
+        let (n, index) = self.stratum_count_and_index();
+        // Grid with at least as many cells as samples, some left empty
+        let nx = f32::sqrt(n as f32).ceil() as u32;
+        let ny = n.div_ceil(nx);
+        let stratum = permutation_element(index, nx * ny, hash as u32);
+        let jitter = mix_bits(hash);
+        (
+            ((stratum % nx) as f32 + unit_float(jitter as u32)) / nx as f32,
+            ((stratum / nx) as f32 + unit_float((jitter >> 32) as u32)) / ny as f32,
+        )
+    }
+}
+
+/// The first primes, used as the bases of the dimensions of the Halton
+/// sequence. Higher dimensions fall back to independent random values.
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// Halton sequence, with the digits of every dimension Owen scrambled by a
+/// hash of the pixel so that neighbouring pixels are not correlated.
+pub struct HaltonSampler {
+    seed: u32,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+    rng: Rng,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            rng: Rng::new(seed as u64),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+        self.rng = Rng::for_sample(self.seed, x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => {
+                let hash = hash(self.pixel.0, self.pixel.1, dimension, self.seed);
+                owen_scrambled_radical_inverse(base, self.index, hash)
+            }
+            None => self.rng.rand(),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// Owen-scrambled Sobol samples, padded across dimensions.
+///
+/// Every 2D sample comes from the first two dimensions of the Sobol sequence,
+/// which are well distributed together, with the sample index shuffled and the
+/// values scrambled independently for every pixel and dimension. The index is
+/// shuffled by Owen scrambling as well, so that the first `2^k` samples of a
+/// pixel are stratified on their own however many follow them.
+pub struct SobolSampler {
+    seed: u32,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn next_hash(&mut self) -> u64 {
+        let hash = hash(self.pixel.0, self.pixel.1, self.dimension, self.seed);
+        self.dimension += 1;
+        hash
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let hash = self.next_hash();
+        let index = owen_scramble(self.index, hash as u32);
+        let scrambled = owen_scramble(index.reverse_bits(), (hash >> 32) as u32);
+        unit_float(scrambled)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let hash = self.next_hash();
+        let index = owen_scramble(self.index, hash as u32);
+        let seeds = mix_bits(hash);
+        (
+            unit_float(owen_scramble(index.reverse_bits(), seeds as u32)),
+            unit_float(owen_scramble(
+                sobol_second_dimension(index),
+                (seeds >> 32) as u32,
+            )),
+        )
+    }
+}
+
+/// Point uniformly distributed on the unit sphere.
+pub fn uniform_sphere(u: (f32, f32)) -> Vec3<f32> {
+    let z = 1.0 - 2.0 * u.0;
+    let r = f32::sqrt(f32::max(0.0, 1.0 - z * z));
+    let phi = 2.0 * misc::PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Point uniformly distributed inside of the unit ball.
+pub fn uniform_ball(u: f32, uv: (f32, f32)) -> Vec3<f32> {
+    uniform_sphere(uv) * u.cbrt()
+}
+
+/// Point uniformly distributed on the unit disk of the xy plane, using the
+/// concentric mapping of Shirley and Chiu that keeps strata compact.
+pub fn concentric_disk(u: (f32, f32)) -> Vec3<f32> {
+    let (x, y) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, misc::PI / 4.0 * (y / x))
+    } else {
+        (y, misc::PI / 2.0 - misc::PI / 4.0 * (x / y))
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
+/// Finalizer of MurmurHash3, spreading every input bit over the output.
+fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5d329728ea185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81dadef4bc2dd44d);
+    v ^= v >> 33;
+    v
+}
+
+fn hash(x: u32, y: u32, dimension: u32, seed: u32) -> u64 {
+    let pixel = ((x as u64) << 32) | y as u64;
+    let key = ((dimension as u64) << 32) | seed as u64;
+    mix_bits(mix_bits(pixel) ^ key)
+}
+
+/// Element with index `i` of a random permutation of `0..l` chosen by `p`,
+/// computed without storing the permutation, after Kensler's "Correlated
+/// Multi-Jittered Sampling".
+fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    i.wrapping_add(p) % l
+}
+
+/// Radical inverse of `index` in the given `base`, with each digit permuted
+/// depending on the digits before it (nested uniform scrambling).
+fn owen_scrambled_radical_inverse(base: u32, mut index: u32, hash: u64) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed: u64 = 0;
+    // Enough digits to resolve every representable value below one
+    while inv_base_m > 1e-8 {
+        let digit = index % base;
+        index /= base;
+        let digit_hash = mix_bits(hash ^ reversed) as u32;
+        let digit = permutation_element(digit, base, digit_hash);
+        reversed = reversed * base as u64 + digit as u64;
+        inv_base_m *= inv_base;
+    }
+    f32::min(
+        (reversed as f64 * inv_base_m) as f32,
+        1.0 - f32::EPSILON / 2.0,
+    )
+}
+
+/// Second dimension of the Sobol sequence, whose generator matrix is the
+/// Pascal triangle modulo two. The first dimension is the bit reversal of the
+/// index.
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut v = 1 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+/// Nested uniform scrambling of the bits of `v`, from most to least
+/// significant, using the Laine-Karras style hash of Burley's "Practical
+/// Hash-based Owen Scrambling".
+fn owen_scramble(mut v: u32, seed: u32) -> u32 {
+    v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x05526c56);
+    v ^= v.wrapping_mul(0x53a22864);
+    v.reverse_bits()
+}
@@ -1,39 +1,52 @@
 use crate::{
+    background::Background,
+    camera::{CameraParams, FocusDistance},
     color::Color,
+    error::Error,
     material::Material,
-    misc::{self, LogLevel},
+    misc::{self, LogLevel, Rng},
     surf::Sphere,
     surf_list::SurfList,
+    triangle::Triangle,
     vec3::Vec3,
 };
-use rand::Rng;
+use std::str::FromStr;
 
 #[derive(Clone, clap::Parser, clap::ValueEnum)]
 pub enum SceneType {
     Basic,
     Complex,
+    Cornell,
 }
 
-impl SceneType {
-    pub fn from(scene_name: &str) -> Self {
-        match scene_name {
-            "basic" => Self::Basic,
-            "complex" => Self::Complex,
-            _ => {
-                misc::log(
-                    LogLevel::Fatal,
-                    &format!(
-                        "Scene name {} not available [options: basic, complex].",
-                        scene_name
-                    ),
-                );
-                std::process::exit(-1);
-            }
+impl FromStr for SceneType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "basic" => Ok(Self::Basic),
+            "complex" => Ok(Self::Complex),
+            "cornell" => Ok(Self::Cornell),
+            _ => Err(Error::UnknownScene(s.to_string())),
         }
     }
 }
 
-fn complex_scene() -> SurfList {
+/// A world of surfaces together with the camera it is viewed from.
+///
+/// Rays that escape the world pick up the light of the `background`. A black
+/// background leaves the scene lit by its emissive materials alone.
+///
+/// The emissive surfaces of the world are also added to `lights`, towards
+/// which shadow rays are sampled at every diffuse hit.
+pub struct Scene {
+    pub world: SurfList,
+    pub lights: SurfList,
+    pub camera: CameraParams,
+    pub background: Background,
+}
+
+fn complex_scene(rng: &mut Rng) -> Scene {
     misc::log(LogLevel::Info, "Creating complex scene");
     let mut world = SurfList::new();
 
@@ -45,27 +58,25 @@ fn complex_scene() -> SurfList {
         ground_material,
     ));
 
-    let mut rng = rand::thread_rng();
-
     // Random spheres
     for a in -11..11 {
         for b in -11..11 {
-            let choose_material = misc::rand();
+            let choose_material = rng.rand();
             let center = Vec3::new(
-                a as f32 + 0.9 * misc::rand(),
+                a as f32 + 0.9 * rng.rand(),
                 0.2,
-                b as f32 + 0.9 * misc::rand(),
+                b as f32 + 0.9 * rng.rand(),
             );
 
             if (center - Vec3::new(4.0, 0.2, 0.0)).len() > 0.9 {
                 let sphere_material = if choose_material < 0.8 {
                     // Lambertian
-                    let albedo = Color::rand(&mut rng) * Color::rand(&mut rng);
+                    let albedo = Color::rand(rng) * Color::rand(rng);
                     Material::lambertian(albedo)
                 } else if choose_material < 0.95 {
                     // Metal
-                    let albedo = Color::rand_on(&mut rng, 0.5, 1.0);
-                    let fuzz = rng.gen_range(0.0..0.5);
+                    let albedo = Color::rand_on(rng, 0.5, 1.0);
+                    let fuzz = rng.rand_on(0.0, 0.5);
                     Material::metal(albedo, fuzz)
                 } else {
                     // Glass
@@ -94,10 +105,22 @@ fn complex_scene() -> SurfList {
         Material::metal(Color::new(0.7, 0.6, 0.5), 0.0),
     ));
 
-    world
+    Scene {
+        world,
+        lights: SurfList::new(),
+        camera: CameraParams {
+            lookfrom: Vec3::new(13.0, 2.0, 3.0),
+            lookat: Vec3::new(0.0, 0.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 20.0,
+            aperture: 0.1,
+            focus_dist: FocusDistance::Fixed(10.0),
+        },
+        background: Background::default(),
+    }
 }
 
-fn basic_scene() -> SurfList {
+fn basic_scene() -> Scene {
     misc::log(LogLevel::Info, "Creating basic scene");
     let material_ground = Material::lambertian(Color::new(0.8, 0.8, 0.0));
     let material_center = Material::lambertian(Color::new(0.1, 0.2, 0.5));
@@ -119,12 +142,158 @@ fn basic_scene() -> SurfList {
     ));
     world.add(Sphere::new(Vec3::new(1.0, 0.0, -1.0), 0.5, material_right));
 
-    world
+    Scene {
+        world,
+        lights: SurfList::new(),
+        camera: CameraParams {
+            lookfrom: Vec3::new(0.0, 1.0, 3.0),
+            lookat: Vec3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 35.0,
+            aperture: 0.1,
+            focus_dist: FocusDistance::Auto,
+        },
+        background: Background::default(),
+    }
+}
+
+/// Add the parallelogram spanned by `u` and `v` from the corner `q`. Its front
+/// face is the one from which `u` turns counter-clockwise into `v`.
+fn add_quad(world: &mut SurfList, q: Vec3<f32>, u: Vec3<f32>, v: Vec3<f32>, material: Material) {
+    world.add_object([
+        Triangle::new(q, q + u, q + u + v, material),
+        Triangle::new(q, q + u + v, q + v, material),
+    ]);
+}
+
+/// Add an axis-aligned box between the corners `a` and `b`, rotated by
+/// `angle` degrees about the vertical axis through its base center.
+fn add_box(world: &mut SurfList, a: Vec3<f32>, b: Vec3<f32>, angle: f32, material: Material) {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let pivot = (a + b) * 0.5;
+    let rotate = |p: Vec3<f32>| {
+        let d = p - pivot;
+        pivot + Vec3::new(cos * d.x() + sin * d.z(), d.y(), -sin * d.x() + cos * d.z())
+    };
+    let corner = |x: f32, y: f32, z: f32| rotate(Vec3::new(x, y, z));
+
+    let (x0, y0, z0) = (a.x(), a.y(), a.z());
+    let (x1, y1, z1) = (b.x(), b.y(), b.z());
+    let faces = [
+        // front, back, left, right, top, bottom, each seen from the inside
+        [
+            corner(x0, y0, z0),
+            corner(x1, y0, z0),
+            corner(x1, y1, z0),
+            corner(x0, y1, z0),
+        ],
+        [
+            corner(x1, y0, z1),
+            corner(x0, y0, z1),
+            corner(x0, y1, z1),
+            corner(x1, y1, z1),
+        ],
+        [
+            corner(x0, y0, z1),
+            corner(x0, y0, z0),
+            corner(x0, y1, z0),
+            corner(x0, y1, z1),
+        ],
+        [
+            corner(x1, y0, z0),
+            corner(x1, y0, z1),
+            corner(x1, y1, z1),
+            corner(x1, y1, z0),
+        ],
+        [
+            corner(x0, y1, z0),
+            corner(x1, y1, z0),
+            corner(x1, y1, z1),
+            corner(x0, y1, z1),
+        ],
+        [
+            corner(x0, y0, z1),
+            corner(x1, y0, z1),
+            corner(x1, y0, z0),
+            corner(x0, y0, z0),
+        ],
+    ];
+    world.add_object(faces.iter().flat_map(|&[p0, p1, p2, p3]| {
+        [
+            Triangle::new(p0, p3, p2, material),
+            Triangle::new(p0, p2, p1, material),
+        ]
+    }));
+}
+
+fn cornell_scene() -> Scene {
+    misc::log(LogLevel::Info, "Creating Cornell box scene");
+    let red = Material::lambertian(Color::new(0.65, 0.05, 0.05));
+    let white = Material::lambertian(Color::new(0.73, 0.73, 0.73));
+    let green = Material::lambertian(Color::new(0.12, 0.45, 0.15));
+    let light = Material::diffuse_light(Color::new(15.0, 15.0, 15.0));
+
+    let mut world = SurfList::new();
+    let x = Vec3::new(555.0, 0.0, 0.0);
+    let y = Vec3::new(0.0, 555.0, 0.0);
+    let z = Vec3::new(0.0, 0.0, 555.0);
+    let origin = Vec3::new(0.0, 0.0, 0.0);
+
+    // Walls, all facing the inside of the box
+    add_quad(&mut world, x, z, y, green);
+    add_quad(&mut world, origin, y, z, red);
+    add_quad(&mut world, origin, z, x, white);
+    add_quad(&mut world, y + z, -z, x, white);
+    add_quad(&mut world, z, y, x, white);
+
+    // Ceiling light, facing down
+    let mut lights = SurfList::new();
+    for list in [&mut world, &mut lights] {
+        add_quad(
+            list,
+            Vec3::new(213.0, 554.0, 227.0),
+            Vec3::new(130.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 105.0),
+            light,
+        );
+    }
+
+    add_box(
+        &mut world,
+        Vec3::new(265.0, 0.0, 295.0),
+        Vec3::new(430.0, 330.0, 460.0),
+        15.0,
+        white,
+    );
+    add_box(
+        &mut world,
+        Vec3::new(130.0, 0.0, 65.0),
+        Vec3::new(295.0, 165.0, 230.0),
+        -18.0,
+        white,
+    );
+
+    Scene {
+        world,
+        lights,
+        camera: CameraParams {
+            lookfrom: Vec3::new(278.0, 278.0, -800.0),
+            lookat: Vec3::new(278.0, 278.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 40.0,
+            aperture: 0.0,
+            focus_dist: FocusDistance::Auto,
+        },
+        background: Background::Solid(Color::default()),
+    }
 }
 
-pub fn make_scene(scene_type: SceneType) -> SurfList {
+/// Build one of the built-in scenes, drawing any random placement of its
+/// surfaces from `rng`.
+pub fn make_scene(scene_type: SceneType, rng: &mut Rng) -> Scene {
     match scene_type {
         SceneType::Basic => basic_scene(),
-        SceneType::Complex => complex_scene(),
+        SceneType::Complex => complex_scene(rng),
+        SceneType::Cornell => cornell_scene(),
     }
 }
@@ -0,0 +1,539 @@
+use crate::{
+    background::{Background, EnvironmentMap},
+    camera::{CameraParams, FocusDistance},
+    color::Color,
+    filter::FilterType,
+    material::Material,
+    obj::{self, ObjError},
+    sampler::SamplerType,
+    scene::Scene,
+    settings::RenderSettings,
+    surf::Sphere,
+    surf_list::SurfList,
+    vec3::Vec3,
+};
+use serde::{de, Deserialize, Deserializer};
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Scene described by a TOML file.
+///
+/// A scene file may contain an optional `[background]` table, an optional
+/// `[settings]` table with the keys `width`, `height`, `spp`, `max_depth`,
+/// `rr_depth`, `seed`, `sampler`, `filter`, `noise_threshold`, `min_spp`,
+/// `max_spp` and `denoise`, an optional `[camera]` table with the keys
+/// `lookfrom`, `lookat`, `vup`, `vfov`, `aperture` and `focus_dist` (either a
+/// number or `"auto"`), and any number of `[[spheres]]` and `[[meshes]]`
+/// entries:
+///
+/// ```toml
+/// [[spheres]]
+/// center = [0.0, -100.5, -1.0]
+/// radius = 100.0
+/// material = { kind = "lambertian", albedo = [0.8, 0.8, 0.0] }
+///
+/// [[meshes]]
+/// path = "teapot.obj"
+/// scale = 0.5
+/// translate = [0.0, 0.0, -1.0]
+/// ```
+///
+/// The available material kinds are `lambertian` (with an `albedo`), `metal`
+/// (with an `albedo` and an optional `fuzz`), `dielectric` (with an
+/// `index_refraction`) and `diffuse_light` (with the radiance it `emit`s).
+///
+/// The background is either a `solid` `color`, a `gradient` from the `bottom`
+/// color to the `top` one, or an equirectangular `environment` map loaded from
+/// an image `path` relative to the scene file, with an optional `rotation` in
+/// degrees about the vertical axis and an optional `intensity` multiplier:
+///
+/// ```toml
+/// [background]
+/// kind = "environment"
+/// path = "sky.hdr"
+/// rotation = 90.0
+/// ```
+///
+/// Without a `[background]` table, the scene is lit by a white to blue sky.
+///
+/// Meshes are loaded from Wavefront OBJ files, whose paths are relative to the
+/// scene file. They are uniformly scaled by the optional `scale` and then
+/// moved by the optional `translate`. The materials of the OBJ file are used
+/// unless a `material` is given for the mesh.
+pub struct SceneFile {
+    pub scene: Scene,
+    pub settings: RenderSettings,
+}
+
+impl SceneFile {
+    /// Load the scene file at `path`, with the files it refers to looked up
+    /// relative to its directory.
+    pub fn load(path: &Path) -> Result<Self, SceneFileError> {
+        let source = fs::read_to_string(path).map_err(|e| SceneFileError::Io {
+            path: path.to_path_buf(),
+            error: e,
+        })?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::parse_in(&source, base_dir).map_err(|e| match e {
+            SceneFileError::Parse { path: None, error } => SceneFileError::Parse {
+                path: Some(path.to_path_buf()),
+                error,
+            },
+            e => e,
+        })
+    }
+
+    /// Parse a scene from the contents of a scene file, with the files it
+    /// refers to looked up relative to the working directory.
+    pub fn parse(source: &str) -> Result<Self, SceneFileError> {
+        Self::parse_in(source, Path::new(""))
+    }
+
+    fn parse_in(source: &str, base_dir: &Path) -> Result<Self, SceneFileError> {
+        let desc: SceneDesc = toml::from_str(source).map_err(|e| SceneFileError::Parse {
+            path: None,
+            error: e,
+        })?;
+
+        let mut world = SurfList::new();
+        let mut lights = SurfList::new();
+        for sphere_desc in desc.spheres {
+            let material = Material::from(sphere_desc.material);
+            let sphere = Sphere::new(sphere_desc.center.into(), sphere_desc.radius, material);
+            if material.is_emissive() {
+                lights.add(sphere);
+            }
+            world.add(sphere);
+        }
+        for mesh_desc in desc.meshes {
+            let default_material = Material::lambertian(Color::new(0.8, 0.8, 0.8));
+            let meshes = obj::load_obj(&base_dir.join(&mesh_desc.path), default_material)
+                .map_err(SceneFileError::Mesh)?;
+            let material = mesh_desc.material.map(Material::from);
+            let translation = mesh_desc
+                .translate
+                .map_or(Vec3::new(0.0, 0.0, 0.0), Vec3::from);
+            for mut mesh in meshes {
+                if let Some(m) = material {
+                    mesh = mesh.with_material(m);
+                }
+                let mesh = mesh.transformed(mesh_desc.scale, translation);
+                // The triangles of a mesh form a single object
+                let emissive = mesh.material().is_emissive();
+                let triangles = mesh.into_triangles();
+                if emissive {
+                    lights.add_object(triangles.clone());
+                }
+                world.add_object(triangles);
+            }
+        }
+
+        Ok(Self {
+            scene: Scene {
+                world,
+                lights,
+                camera: desc.camera.into(),
+                background: match desc.background {
+                    Some(background) => background.load(base_dir)?,
+                    None => Background::default(),
+                },
+            },
+            settings: desc.settings.into(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// The scene is not valid TOML or does not describe a scene. The path is
+    /// missing when the scene was parsed from a string.
+    Parse {
+        path: Option<PathBuf>,
+        error: toml::de::Error,
+    },
+    Mesh(ObjError),
+    Background {
+        path: PathBuf,
+        error: image::ImageError,
+    },
+}
+
+impl Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => {
+                write!(f, "unable to read scene file {}: {}", path.display(), error)
+            }
+            Self::Parse {
+                path: Some(path),
+                error,
+            } => {
+                write!(f, "invalid scene file {}:\n{}", path.display(), error)
+            }
+            Self::Parse { path: None, error } => write!(f, "invalid scene file:\n{}", error),
+            Self::Mesh(error) => write!(f, "unable to load mesh: {}", error),
+            Self::Background { path, error } => write!(
+                f,
+                "unable to load environment map {}: {}",
+                path.display(),
+                error
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {}
+
+/// Vectors and colors are written as arrays of exactly three numbers.
+#[derive(Deserialize)]
+struct Triple([f32; 3]);
+
+impl From<Triple> for Vec3<f32> {
+    fn from(t: Triple) -> Self {
+        Vec3::new(t.0[0], t.0[1], t.0[2])
+    }
+}
+
+impl From<Triple> for Color {
+    fn from(t: Triple) -> Self {
+        Color::new(t.0[0], t.0[1], t.0[2])
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    background: Option<BackgroundDesc>,
+    #[serde(default)]
+    settings: SettingsDesc,
+    #[serde(default)]
+    camera: CameraDesc,
+    #[serde(default)]
+    spheres: Vec<SphereDesc>,
+    #[serde(default)]
+    meshes: Vec<MeshDesc>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct SettingsDesc {
+    #[serde(default, deserialize_with = "dimension")]
+    width: Option<u32>,
+    #[serde(default, deserialize_with = "dimension")]
+    height: Option<u32>,
+    #[serde(default, deserialize_with = "count")]
+    spp: Option<u32>,
+    #[serde(default, deserialize_with = "count")]
+    max_depth: Option<u32>,
+    rr_depth: Option<u32>,
+    seed: Option<u32>,
+    #[serde(default, deserialize_with = "sampler")]
+    sampler: Option<SamplerType>,
+    #[serde(default, deserialize_with = "filter")]
+    filter: Option<FilterType>,
+    #[serde(default, deserialize_with = "threshold")]
+    noise_threshold: Option<f32>,
+    #[serde(default, deserialize_with = "count")]
+    min_spp: Option<u32>,
+    #[serde(default, deserialize_with = "count")]
+    max_spp: Option<u32>,
+    denoise: Option<bool>,
+}
+
+impl From<SettingsDesc> for RenderSettings {
+    fn from(desc: SettingsDesc) -> Self {
+        let default = RenderSettings::default();
+        RenderSettings {
+            image_width: desc.width.map_or(default.image_width, |w| w as usize),
+            image_height: desc.height.map_or(default.image_height, |h| h as usize),
+            samples_per_pixel: desc.spp.unwrap_or(default.samples_per_pixel),
+            max_depth: desc.max_depth.unwrap_or(default.max_depth),
+            rr_depth: desc.rr_depth.unwrap_or(default.rr_depth),
+            seed: desc.seed.unwrap_or(default.seed),
+            sampler: desc.sampler.unwrap_or(default.sampler),
+            filter: desc.filter.unwrap_or(default.filter),
+            noise_threshold: desc.noise_threshold.or(default.noise_threshold),
+            min_spp: desc.min_spp.unwrap_or(default.min_spp),
+            max_spp: desc.max_spp.unwrap_or(default.max_spp),
+            spp_heatmap: default.spp_heatmap,
+            denoise: desc.denoise.unwrap_or(default.denoise),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    lookfrom: Option<Triple>,
+    lookat: Option<Triple>,
+    vup: Option<Triple>,
+    vfov: Option<f32>,
+    aperture: Option<f32>,
+    #[serde(default, deserialize_with = "focus_distance")]
+    focus_dist: Option<FocusDistance>,
+}
+
+impl From<CameraDesc> for CameraParams {
+    fn from(desc: CameraDesc) -> Self {
+        let default = CameraParams::default();
+        CameraParams {
+            lookfrom: desc.lookfrom.map_or(default.lookfrom, Vec3::from),
+            lookat: desc.lookat.map_or(default.lookat, Vec3::from),
+            vup: desc.vup.map_or(default.vup, Vec3::from),
+            vfov: desc.vfov.unwrap_or(default.vfov),
+            aperture: desc.aperture.unwrap_or(default.aperture),
+            focus_dist: desc.focus_dist.unwrap_or(default.focus_dist),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundDesc {
+    Solid {
+        color: Triple,
+    },
+    Gradient {
+        bottom: Triple,
+        top: Triple,
+    },
+    Environment {
+        path: PathBuf,
+        #[serde(default)]
+        rotation: f32,
+        #[serde(default = "unit_scale", deserialize_with = "positive")]
+        intensity: f32,
+    },
+}
+
+impl BackgroundDesc {
+    fn load(self, base_dir: &Path) -> Result<Background, SceneFileError> {
+        Ok(match self {
+            Self::Solid { color } => Background::Solid(color.into()),
+            Self::Gradient { bottom, top } => Background::Gradient {
+                bottom: bottom.into(),
+                top: top.into(),
+            },
+            Self::Environment {
+                path,
+                rotation,
+                intensity,
+            } => {
+                let path = base_dir.join(path);
+                let map = EnvironmentMap::load(&path)
+                    .map_err(|e| SceneFileError::Background { path, error: e })?;
+                Background::EnvMap(map.with_rotation(rotation).with_intensity(intensity))
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereDesc {
+    center: Triple,
+    radius: f32,
+    material: MaterialDesc,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDesc {
+    path: PathBuf,
+    #[serde(default = "unit_scale", deserialize_with = "positive")]
+    scale: f32,
+    translate: Option<Triple>,
+    material: Option<MaterialDesc>,
+}
+
+fn unit_scale() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian {
+        albedo: Triple,
+    },
+    Metal {
+        albedo: Triple,
+        #[serde(default)]
+        fuzz: f32,
+    },
+    Dielectric {
+        index_refraction: f32,
+    },
+    DiffuseLight {
+        emit: Triple,
+    },
+}
+
+impl From<MaterialDesc> for Material {
+    fn from(desc: MaterialDesc) -> Self {
+        match desc {
+            MaterialDesc::Lambertian { albedo } => Material::lambertian(albedo.into()),
+            MaterialDesc::Metal { albedo, fuzz } => Material::metal(albedo.into(), fuzz),
+            MaterialDesc::Dielectric { index_refraction } => Material::dielectric(index_refraction),
+            MaterialDesc::DiffuseLight { emit } => Material::diffuse_light(emit.into()),
+        }
+    }
+}
+
+fn at_least<'de, D: Deserializer<'de>>(deserializer: D, min: u32) -> Result<Option<u32>, D::Error> {
+    let value = u32::deserialize(deserializer)?;
+    if value < min {
+        return Err(de::Error::custom(format!(
+            "expected a value of at least {}, found {}",
+            min, value
+        )));
+    }
+    Ok(Some(value))
+}
+
+/// The focus distance is either a positive number or the string `"auto"`.
+fn focus_distance<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<FocusDistance>, D::Error> {
+    struct FocusVisitor;
+
+    impl<'de> de::Visitor<'de> for FocusVisitor {
+        type Value = FocusDistance;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a positive number or \"auto\"")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            v.parse().map_err(E::custom)
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+            self.visit_str(&v.to_string())
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+            self.visit_str(&v.to_string())
+        }
+    }
+
+    deserializer.deserialize_any(FocusVisitor).map(Some)
+}
+
+/// Samplers are given by name, as on the command line.
+fn sampler<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<SamplerType>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    name.parse().map(Some).map_err(de::Error::custom)
+}
+
+/// Filters are given by name, as on the command line.
+fn filter<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<FilterType>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    name.parse().map(Some).map_err(de::Error::custom)
+}
+
+fn positive<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let value = f32::deserialize(deserializer)?;
+    if value <= 0.0 {
+        return Err(de::Error::custom(format!(
+            "expected a positive value, found {}",
+            value
+        )));
+    }
+    Ok(value)
+}
+
+fn threshold<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    positive(deserializer).map(Some)
+}
+
+/// Image dimensions need at least two pixels.
+fn dimension<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    at_least(deserializer, 2)
+}
+
+fn count<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    at_least(deserializer, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Line of the error reported when parsing `source`, counting from one.
+    fn error_line(source: &str) -> usize {
+        match SceneFile::parse(source) {
+            Err(SceneFileError::Parse { error, .. }) => {
+                let span = error.span().expect("error without a location");
+                source[..span.start].matches('\n').count() + 1
+            }
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("parsing succeeded"),
+        }
+    }
+
+    #[test]
+    fn unknown_material_kind_is_reported_at_its_line() {
+        let source = concat!(
+            "[[spheres]]\n",
+            "center = [0.0, 0.0, -1.0]\n",
+            "radius = 0.5\n",
+            "material = { kind = \"plastic\" }\n",
+        );
+        assert_eq!(error_line(source), 4);
+    }
+
+    #[test]
+    fn triangles_of_a_mesh_share_an_object() {
+        let dir = std::env::temp_dir().join(format!("radiant-scene-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let obj = dir.join("quad.obj");
+        fs::write(
+            &obj,
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\nf 1 3 4\n",
+        )
+        .unwrap();
+        let source = format!(
+            concat!(
+                "[[spheres]]\n",
+                "center = [0.0, 0.0, -1.0]\n",
+                "radius = 0.5\n",
+                "material = {{ kind = \"dielectric\", index_refraction = 1.5 }}\n",
+                "[[meshes]]\n",
+                "path = {:?}\n",
+            ),
+            obj
+        );
+        let scene_file = SceneFile::parse(&source);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let ids: Vec<u32> = match scene_file {
+            Ok(scene_file) => scene_file
+                .scene
+                .world
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect(),
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(ids, [1, 2, 2, 2]);
+    }
+
+    #[test]
+    fn malformed_vector_is_reported_at_its_line() {
+        let source = concat!(
+            "[[spheres]]\n",
+            "radius = 0.5\n",
+            "material = { kind = \"lambertian\", albedo = [0.5, 0.5, 0.5] }\n",
+            "center = [0.0, -1.0]\n",
+        );
+        assert_eq!(error_line(source), 4);
+    }
+}
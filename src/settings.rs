@@ -0,0 +1,122 @@
+use crate::{
+    error::{Error, Result},
+    filter::FilterType,
+    sampler::SamplerType,
+};
+
+/// Default width of the rendered image.
+pub const DEFAULT_IMAGE_WIDTH: usize = 1024;
+
+/// Default height of the rendered image, giving a 16:9 aspect ratio together
+/// with the default width.
+pub const DEFAULT_IMAGE_HEIGHT: usize = 576;
+
+/// Default number of samples used per pixel in the process of antialiasing.
+pub const DEFAULT_SAMPLES_PER_PIXEL: u32 = 20;
+
+/// Default maximum number of bounces of a ray. Russian roulette ends nearly
+/// every path well before, so this is only a safety limit.
+pub const DEFAULT_MAX_DEPTH: u32 = 256;
+
+/// Default number of bounces after which paths are terminated by Russian
+/// roulette.
+pub const DEFAULT_RR_DEPTH: u32 = 3;
+
+/// Default seed of the random numbers of a render.
+pub const DEFAULT_SEED: u32 = 0;
+
+/// Default number of samples every pixel takes before adaptive sampling
+/// decides whether it has converged.
+pub const DEFAULT_MIN_SPP: u32 = 8;
+
+/// Default number of samples after which adaptive sampling stops refining a
+/// pixel.
+pub const DEFAULT_MAX_SPP: u32 = 1024;
+
+/// Parameters controlling the quality and dimensions of a render.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderSettings {
+    /// Width of the output image in pixels.
+    pub image_width: usize,
+    /// Height of the output image in pixels.
+    pub image_height: usize,
+    /// Number of samples taken for each pixel, or on average over the image
+    /// when sampling adaptively.
+    pub samples_per_pixel: u32,
+    /// Maximum number of bounces of a ray.
+    pub max_depth: u32,
+    /// Number of bounces after which paths may be terminated by Russian
+    /// roulette.
+    pub rr_depth: u32,
+    /// Seed of the random numbers. Renders with the same seed and settings
+    /// are identical.
+    pub seed: u32,
+    /// Sampler drawing the sample values of every pixel.
+    pub sampler: SamplerType,
+    /// Filter weighting the samples splatted into the pixels around them.
+    pub filter: FilterType,
+    /// Relative error of the mean luminance below which a pixel counts as
+    /// converged. Setting it enables adaptive sampling, which stops the
+    /// converged pixels early and spends the rest of the samples on the noisy
+    /// ones.
+    pub noise_threshold: Option<f32>,
+    /// Number of samples every pixel takes when sampling adaptively.
+    pub min_spp: u32,
+    /// Largest number of samples a pixel takes when sampling adaptively.
+    pub max_spp: u32,
+    /// Also write an image of the number of samples taken by each pixel.
+    pub spp_heatmap: bool,
+    /// Filter the noise out of the finished image, guided by the albedo and
+    /// normal of the first hits.
+    pub denoise: bool,
+}
+
+impl RenderSettings {
+    /// Aspect ratio derived from the image dimensions.
+    pub fn aspect_ratio(&self) -> f32 {
+        self.image_width as f32 / self.image_height as f32
+    }
+
+    /// Check that the settings can be rendered with.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| Err(Error::InvalidSettings(reason.to_string()));
+        if self.image_width < 2 || self.image_height < 2 {
+            return invalid("the image needs at least two pixels in each dimension");
+        }
+        if self.samples_per_pixel == 0 {
+            return invalid("the number of samples per pixel must be positive");
+        }
+        if self.max_depth == 0 {
+            return invalid("the maximum depth must be positive");
+        }
+        if let Some(threshold) = self.noise_threshold {
+            if !(threshold > 0.0 && threshold.is_finite()) {
+                return invalid("the noise threshold must be positive");
+            }
+            if self.min_spp == 0 || self.max_spp == 0 {
+                return invalid("the minimum and maximum samples per pixel must be positive");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            image_width: DEFAULT_IMAGE_WIDTH,
+            image_height: DEFAULT_IMAGE_HEIGHT,
+            samples_per_pixel: DEFAULT_SAMPLES_PER_PIXEL,
+            max_depth: DEFAULT_MAX_DEPTH,
+            rr_depth: DEFAULT_RR_DEPTH,
+            seed: DEFAULT_SEED,
+            sampler: SamplerType::default(),
+            filter: FilterType::default(),
+            noise_threshold: None,
+            min_spp: DEFAULT_MIN_SPP,
+            max_spp: DEFAULT_MAX_SPP,
+            spp_heatmap: false,
+            denoise: false,
+        }
+    }
+}
@@ -1,13 +1,17 @@
-pub use crate::{material::Material, ray::Ray, vec3::Vec3};
+pub use crate::{aabb::Aabb, material::Material, ray::Ray, vec3::Vec3};
+use crate::{misc, sampler};
 
 // Note: this HitRecord takes the approach of calculating whether the ray hits
 // from the front or back of the surface on the coloring.
+#[derive(Clone, Copy)]
 pub struct HitRecord {
     point: Vec3<f32>,
     normal: Vec3<f32>,
     material: Material,
     parameter: f32,
     front_face: bool,
+    uv: (f32, f32),
+    object_id: u32,
 }
 
 impl HitRecord {
@@ -19,11 +23,29 @@ impl HitRecord {
             material: Material::default(),
             parameter: 0.0,
             front_face: true,
+            uv: (0.0, 0.0),
+            object_id: 0,
         }
     }
 
+    /// Record that `ray` hit a surface of the given `material` at the ray
+    /// `parameter`, where the surface has the given outward pointing normal.
+    pub fn record(
+        &mut self,
+        ray: &Ray,
+        parameter: f32,
+        outward_normal: Vec3<f32>,
+        material: Material,
+    ) {
+        self.parameter = parameter;
+        self.point = ray.point_at(parameter);
+        self.set_face_normal(ray, outward_normal);
+        self.material = material;
+        self.uv = (0.0, 0.0);
+    }
+
     // The normal should point always oposite to the incoming ray
-    fn set_face_normal(&mut self, ray: &Ray, outward_normal: Vec3<f32>) {
+    pub fn set_face_normal(&mut self, ray: &Ray, outward_normal: Vec3<f32>) {
         self.front_face = ray.direction().dot(&outward_normal) < 0.0;
         self.normal = if self.front_face {
             outward_normal
@@ -32,6 +54,21 @@ impl HitRecord {
         };
     }
 
+    /// Replace the normal by an interpolated shading normal, flipped if needed
+    /// to lie on the same side of the surface as the geometric normal.
+    pub fn set_shading_normal(&mut self, shading_normal: Vec3<f32>) {
+        self.normal = if shading_normal.dot(&self.normal) < 0.0 {
+            -shading_normal
+        } else {
+            shading_normal
+        };
+    }
+
+    /// Set the texture coordinates of the hit point.
+    pub fn set_uv(&mut self, u: f32, v: f32) {
+        self.uv = (u, v);
+    }
+
     pub fn point(&self) -> Vec3<f32> {
         self.point
     }
@@ -51,6 +88,20 @@ impl HitRecord {
     pub fn front_face(&self) -> bool {
         self.front_face
     }
+
+    pub fn uv(&self) -> (f32, f32) {
+        self.uv
+    }
+
+    /// Set the identifier of the object hit, as given by the list holding it.
+    pub fn set_object_id(&mut self, object_id: u32) {
+        self.object_id = object_id;
+    }
+
+    /// Identifier of the object hit, zero if it was not hit through a list.
+    pub fn object_id(&self) -> u32 {
+        self.object_id
+    }
 }
 
 impl Default for HitRecord {
@@ -59,8 +110,26 @@ impl Default for HitRecord {
     }
 }
 
-pub trait Surface {
+/// Geometry that can be intersected by rays. Surfaces are shared between the
+/// rendering threads, hence the `Send + Sync` requirement.
+pub trait Surface: Send + Sync {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool;
+
+    /// Box enclosing the whole surface, or `None` if the surface is unbounded.
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Density, with respect to solid angle as seen from `origin`, with which
+    /// `random_direction` picks `direction`. Only surfaces that are sampled as
+    /// light sources need to implement it.
+    fn pdf_value(&self, _origin: Vec3<f32>, _direction: Vec3<f32>) -> f32 {
+        0.0
+    }
+
+    /// Random direction from `origin` towards the surface, given the sample
+    /// values `u` and `uv` uniformly distributed in [0, 1).
+    fn random_direction(&self, _origin: Vec3<f32>, _u: f32, _uv: (f32, f32)) -> Vec3<f32> {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -113,15 +182,65 @@ impl Surface for Sphere {
 
             match root {
                 Some(r) => {
-                    rec.parameter = r;
-                    rec.point = ray.point_at(r);
-                    let outward_normal: Vec3<f32> = (rec.point - self.center) / self.radius;
-                    rec.set_face_normal(ray, outward_normal);
-                    rec.material = self.material;
+                    let outward_normal: Vec3<f32> = (ray.point_at(r) - self.center) / self.radius;
+                    rec.record(ray, r, outward_normal, self.material);
+
+                    // Spherical coordinates of the hit point as texture coordinates
+                    let theta = f32::acos((-outward_normal.y()).clamp(-1.0, 1.0));
+                    let phi = f32::atan2(-outward_normal.z(), outward_normal.x()) + misc::PI;
+                    rec.set_uv(phi / (2.0 * misc::PI), theta / misc::PI);
                     true
                 }
                 None => false,
             }
         }
     }
+
+    // Uniform over the cone of directions subtended by the sphere, or over all
+    // directions when `origin` is inside of it
+    fn pdf_value(&self, origin: Vec3<f32>, direction: Vec3<f32>) -> f32 {
+        let mut rec = HitRecord::new();
+        if !self.hit(&Ray::new(origin, direction), 0.001, misc::INFTY, &mut rec) {
+            return 0.0;
+        }
+        let dist_squared = (self.center - origin).len_squared();
+        let radius_squared = self.radius * self.radius;
+        if dist_squared <= radius_squared {
+            return 1.0 / (4.0 * misc::PI);
+        }
+        let cos_theta_max = f32::sqrt(1.0 - radius_squared / dist_squared);
+        1.0 / (2.0 * misc::PI * (1.0 - cos_theta_max))
+    }
+
+    fn random_direction(&self, origin: Vec3<f32>, _u: f32, uv: (f32, f32)) -> Vec3<f32> {
+        let to_center = self.center - origin;
+        let dist_squared = to_center.len_squared();
+        let radius_squared = self.radius * self.radius;
+        if dist_squared <= radius_squared {
+            return sampler::uniform_sphere(uv);
+        }
+
+        let cos_theta_max = f32::sqrt(1.0 - radius_squared / dist_squared);
+        let cos_theta = 1.0 + uv.0 * (cos_theta_max - 1.0);
+        let sin_theta = f32::sqrt(1.0 - cos_theta * cos_theta);
+        let phi = 2.0 * misc::PI * uv.1;
+
+        // Orthonormal basis around the direction to the center
+        let w = to_center.unit_vector();
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(&a).unit_vector();
+        let u = w.cross(&v);
+        u * (phi.cos() * sin_theta) + v * (phi.sin() * sin_theta) + w * cos_theta
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // The radius is negative for spheres with inward pointing normals
+        let r = self.radius.abs();
+        let extent = Vec3::new(r, r, r);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
 }
@@ -1,36 +1,99 @@
 use crate::{
+    aabb::Aabb,
     ray::Ray,
-    surf::{HitRecord, Sphere, Surface},
+    surf::{HitRecord, Surface},
+    vec3::Vec3,
 };
 
-// `SurfList` accepts a vector of `Sphere` of the same material
+// `SurfList` accepts any kind of `Surface`, including other lists
+//
+// Every surface belongs to an object, identified by a number counting from
+// one in the order the objects are added. Hits report the identifier of the
+// object through the hit record.
 pub struct SurfList {
-    list: Vec<Sphere>,
+    list: Vec<Box<dyn Surface>>,
+    object_ids: Vec<u32>,
+    objects: u32,
 }
 
 impl SurfList {
     pub fn new() -> Self {
-        Self { list: Vec::new() }
+        Self {
+            list: Vec::new(),
+            object_ids: Vec::new(),
+            objects: 0,
+        }
+    }
+
+    /// Add a surface forming an object of its own.
+    pub fn add<S: Surface + 'static>(&mut self, surf: S) {
+        self.add_object([surf]);
     }
 
-    pub fn add(&mut self, surf: Sphere) {
-        self.list.push(surf);
+    /// Add several surfaces forming a single object, such as the triangles of
+    /// a mesh.
+    pub fn add_object<S: Surface + 'static>(&mut self, parts: impl IntoIterator<Item = S>) {
+        self.objects += 1;
+        for surf in parts {
+            self.list.push(Box::new(surf));
+            self.object_ids.push(self.objects);
+        }
     }
 
-    pub fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+}
+
+impl Surface for SurfList {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
         let mut temp_rec: HitRecord = HitRecord::new();
         let mut hit_anything = false;
         let mut closest_so_far = t_max;
 
-        for surf in self.list.iter() {
+        for (surf, object_id) in self.list.iter().zip(&self.object_ids) {
             if surf.hit(ray, t_min, closest_so_far, &mut temp_rec) {
                 hit_anything = true;
                 closest_so_far = temp_rec.parameter();
+                temp_rec.set_object_id(*object_id);
             }
         }
-        *rec = temp_rec;
+        if hit_anything {
+            *rec = temp_rec;
+        }
         hit_anything
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut boxes = self.list.iter().map(|surf| surf.bounding_box());
+        let first = boxes.next()??;
+        boxes.try_fold(first, |acc, bbox| Some(acc.surrounding(&bbox?)))
+    }
+
+    // Each surface of the list is picked with the same probability
+    fn pdf_value(&self, origin: Vec3<f32>, direction: Vec3<f32>) -> f32 {
+        if self.list.is_empty() {
+            return 0.0;
+        }
+        let sum: f32 = self
+            .list
+            .iter()
+            .map(|surf| surf.pdf_value(origin, direction))
+            .sum();
+        sum / self.list.len() as f32
+    }
+
+    fn random_direction(&self, origin: Vec3<f32>, u: f32, uv: (f32, f32)) -> Vec3<f32> {
+        let scaled = u * self.list.len() as f32;
+        let index = (scaled as usize).min(self.list.len() - 1);
+        // What remains of `u` is still uniform, for nested lists
+        let u = (scaled - index as f32).min(1.0 - f32::EPSILON);
+        self.list[index].random_direction(origin, u, uv)
+    }
 }
 
 impl Default for SurfList {
@@ -39,29 +102,41 @@ impl Default for SurfList {
     }
 }
 
-pub struct SurfListIntoIter {
-    index: usize,
-    list: Vec<Sphere>,
-}
-
-impl Iterator for SurfListIntoIter {
-    type Item = Sphere;
+/// The surfaces of the list, paired with the identifier of their object.
+impl IntoIterator for SurfList {
+    type Item = (u32, Box<dyn Surface>);
+    type IntoIter = std::iter::Zip<std::vec::IntoIter<u32>, std::vec::IntoIter<Box<dyn Surface>>>;
 
-    fn next(&mut self) -> Option<Sphere> {
-        let temp_index = self.index;
-        self.index += 1;
-        Some(self.list[temp_index])
+    fn into_iter(self) -> Self::IntoIter {
+        self.object_ids.into_iter().zip(self.list)
     }
 }
 
-impl IntoIterator for SurfList {
-    type Item = Sphere;
-    type IntoIter = SurfListIntoIter;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Material, surf::Sphere};
 
-    fn into_iter(self) -> Self::IntoIter {
-        SurfListIntoIter {
-            list: self.list,
-            index: 0,
-        }
+    #[test]
+    fn inner_list_missing_keeps_earlier_hit() {
+        let mut inner = SurfList::new();
+        inner.add(Sphere::new(
+            Vec3::new(0.0, 10.0, -5.0),
+            1.0,
+            Material::default(),
+        ));
+        let mut outer = SurfList::new();
+        outer.add(Sphere::new(
+            Vec3::new(0.0, 0.0, -5.0),
+            1.0,
+            Material::default(),
+        ));
+        outer.add(inner);
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::new();
+        assert!(outer.hit(&ray, 0.001, f32::INFINITY, &mut rec));
+        assert!((rec.parameter() - 4.0).abs() < 1e-4);
+        assert_eq!(rec.object_id(), 1);
     }
 }
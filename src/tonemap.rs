@@ -0,0 +1,127 @@
+use crate::color::Color;
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// Curve compressing linear radiance into the displayable range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapOperator {
+    /// Leave the radiance untouched, clipping it at one.
+    #[default]
+    Linear,
+    /// Reinhard's `L / (1 + L)` curve on the luminance, which never reaches
+    /// white.
+    Reinhard,
+    /// Reinhard's curve extended so that the brightest pixel of the image maps
+    /// to white.
+    ExtendedReinhard,
+    /// Narkowicz's fit of the ACES filmic curve, on every channel.
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2, on every channel.
+    Hable,
+}
+
+impl ToneMapOperator {
+    /// Map the exposed `color`. `max_luminance` is the luminance of the
+    /// brightest pixel of the exposed image.
+    fn apply(self, color: Color, max_luminance: f32) -> Color {
+        match self {
+            Self::Linear => color,
+            Self::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            Self::ExtendedReinhard => {
+                let white_squared = max_luminance.max(1.0).powi(2);
+                scale_luminance(color, |l| l * (1.0 + l / white_squared) / (1.0 + l))
+            }
+            Self::Aces => color.map(|c| {
+                let c = c.max(0.0);
+                (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)
+            }),
+            Self::Hable => {
+                // Linear white point, and the exposure bias the curve was tuned for
+                const WHITE: f32 = 11.2;
+                const BIAS: f32 = 2.0;
+                let scale = 1.0 / hable(WHITE);
+                color.map(|c| hable(BIAS * c.max(0.0)) * scale)
+            }
+        }
+    }
+}
+
+impl FromStr for ToneMapOperator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "linear" => Ok(Self::Linear),
+            "reinhard" => Ok(Self::Reinhard),
+            "extended-reinhard" => Ok(Self::ExtendedReinhard),
+            "aces" => Ok(Self::Aces),
+            "hable" => Ok(Self::Hable),
+            _ => Err(format!(
+                concat!(
+                    "unknown tone mapping operator `{}`, expected `linear`, `reinhard`, ",
+                    "`extended-reinhard`, `aces` or `hable`"
+                ),
+                s
+            )),
+        }
+    }
+}
+
+impl Display for ToneMapOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Linear => "linear",
+            Self::Reinhard => "reinhard",
+            Self::ExtendedReinhard => "extended-reinhard",
+            Self::Aces => "aces",
+            Self::Hable => "hable",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Conversion of linear radiance into display values in `[0, 1]`, which are
+/// still to be encoded with the sRGB transfer function.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ToneMap {
+    pub operator: ToneMapOperator,
+    /// Exposure adjustment in stops; every stop doubles the radiance.
+    pub exposure: f32,
+}
+
+impl ToneMap {
+    pub fn new(operator: ToneMapOperator, exposure: f32) -> Self {
+        Self { operator, exposure }
+    }
+
+    /// Tone map `color`, where `max_luminance` is the luminance of the
+    /// brightest pixel of the image before exposure.
+    pub fn apply(&self, color: Color, max_luminance: f32) -> Color {
+        let scale = self.exposure.exp2();
+        self.operator
+            .apply(color * scale, max_luminance * scale)
+            .map(|c| c.clamp(0.0, 1.0))
+    }
+}
+
+/// Scale the channels of `color` so that its luminance `l` becomes `f(l)`.
+fn scale_luminance(color: Color, f: impl Fn(f32) -> f32) -> Color {
+    let luminance = color.luminance();
+    if luminance > 0.0 {
+        color * (f(luminance) / luminance)
+    } else {
+        color
+    }
+}
+
+fn hable(x: f32) -> f32 {
+    const A: f32 = 0.15; // Shoulder strength
+    const B: f32 = 0.50; // Linear strength
+    const C: f32 = 0.10; // Linear angle
+    const D: f32 = 0.20; // Toe strength
+    const E: f32 = 0.02; // Toe numerator
+    const F: f32 = 0.30; // Toe denominator
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
@@ -0,0 +1,143 @@
+use crate::{
+    aabb::Aabb,
+    material::Material,
+    misc,
+    ray::Ray,
+    surf::{HitRecord, Surface},
+    vec3::Vec3,
+};
+
+/// Möller–Trumbore ray-triangle intersection.
+///
+/// Returns the ray parameter of the hit together with the barycentric
+/// coordinates `(b1, b2)` of the hit point with respect to `v1` and `v2`. The
+/// coordinate with respect to `v0` is `1 - b1 - b2`.
+pub fn intersect(
+    ray: &Ray,
+    v0: Vec3<f32>,
+    v1: Vec3<f32>,
+    v2: Vec3<f32>,
+    t_min: f32,
+    t_max: f32,
+) -> Option<(f32, f32, f32)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let pvec = ray.direction().cross(&edge2);
+    let det = edge1.dot(&pvec);
+
+    // The ray is parallel to the plane of the triangle. The determinant scales
+    // with the lengths of the edges and of the direction, so small triangles
+    // need a threshold relative to them.
+    let scale = edge1.len() * edge2.len() * ray.direction().len();
+    if det.abs() <= f32::EPSILON * scale {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = ray.origin() - v0;
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(&edge1);
+    let b2 = ray.direction().dot(&qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(&qvec) * inv_det;
+    if t < t_min || t_max < t {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+/// Density, with respect to solid angle as seen from `origin`, of the
+/// directions picked by `random_direction` towards the same triangle.
+pub fn pdf_value(
+    origin: Vec3<f32>,
+    direction: Vec3<f32>,
+    v0: Vec3<f32>,
+    v1: Vec3<f32>,
+    v2: Vec3<f32>,
+) -> f32 {
+    let ray = Ray::new(origin, direction);
+    match intersect(&ray, v0, v1, v2, 0.001, misc::INFTY) {
+        Some((t, _, _)) => {
+            let normal = (v1 - v0).cross(&(v2 - v0));
+            let area = 0.5 * normal.len();
+            let dist_squared = t * t * direction.len_squared();
+            let cos = (direction.dot(&normal) / (direction.len() * 2.0 * area)).abs();
+            dist_squared / (cos * area)
+        }
+        None => 0.0,
+    }
+}
+
+/// Direction from `origin` towards a point picked uniformly over the area of
+/// the triangle.
+pub fn random_direction(
+    origin: Vec3<f32>,
+    v0: Vec3<f32>,
+    v1: Vec3<f32>,
+    v2: Vec3<f32>,
+    uv: (f32, f32),
+) -> Vec3<f32> {
+    let sqrt_r1 = uv.0.sqrt();
+    let r2 = uv.1;
+    let b1 = sqrt_r1 * (1.0 - r2);
+    let b2 = sqrt_r1 * r2;
+    let point = v0 + (v1 - v0) * b1 + (v2 - v0) * b2;
+    point - origin
+}
+
+/// Flat shaded triangle. The front face is the one from which the vertices are
+/// seen in counter-clockwise order.
+#[derive(Clone, Copy)]
+pub struct Triangle {
+    vertices: [Vec3<f32>; 3],
+    material: Material,
+}
+
+impl Triangle {
+    pub fn new(v0: Vec3<f32>, v1: Vec3<f32>, v2: Vec3<f32>, material: Material) -> Self {
+        Self {
+            vertices: [v0, v1, v2],
+            material,
+        }
+    }
+
+    pub fn vertices(&self) -> [Vec3<f32>; 3] {
+        self.vertices
+    }
+}
+
+impl Surface for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        let [v0, v1, v2] = self.vertices;
+        match intersect(ray, v0, v1, v2, t_min, t_max) {
+            Some((t, b1, b2)) => {
+                let outward_normal = (v1 - v0).cross(&(v2 - v0)).unit_vector();
+                rec.record(ray, t, outward_normal, self.material);
+                rec.set_uv(b1, b2);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&self.vertices))
+    }
+
+    fn pdf_value(&self, origin: Vec3<f32>, direction: Vec3<f32>) -> f32 {
+        let [v0, v1, v2] = self.vertices;
+        pdf_value(origin, direction, v0, v1, v2)
+    }
+
+    fn random_direction(&self, origin: Vec3<f32>, _u: f32, uv: (f32, f32)) -> Vec3<f32> {
+        let [v0, v1, v2] = self.vertices;
+        random_direction(origin, v0, v1, v2, uv)
+    }
+}
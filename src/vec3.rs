@@ -1,12 +1,12 @@
-use rand::{rngs::ThreadRng, Rng};
-
-use crate::misc;
+use crate::misc::Rng;
 use std::{
+    convert::TryFrom,
     iter::{IntoIterator, Iterator},
     ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub},
+    str::FromStr,
 };
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Vec3<T> {
     x: T,
     y: T,
@@ -84,20 +84,20 @@ impl Vec3<f32> {
         refrac_perpendicular + refrac_parallel
     }
 
-    pub fn random() -> Self {
-        Self::new(misc::rand(), misc::rand(), misc::rand())
+    pub fn random(rng: &mut Rng) -> Self {
+        Self::new(rng.rand(), rng.rand(), rng.rand())
     }
 
-    pub fn random_on(rng: &mut ThreadRng, min: f32, max: f32) -> Self {
+    pub fn random_on(rng: &mut Rng, min: f32, max: f32) -> Self {
         Self::new(
-            rng.gen_range(min..max),
-            rng.gen_range(min..max),
-            rng.gen_range(min..max),
+            rng.rand_on(min, max),
+            rng.rand_on(min, max),
+            rng.rand_on(min, max),
         )
     }
 
     // Hacky incorrect method for diffusion
-    pub fn random_unit_sphere(rng: &mut ThreadRng) -> Self {
+    pub fn random_unit_sphere(rng: &mut Rng) -> Self {
         loop {
             let rand = Self::random_on(rng, -1.0, 1.0);
             if rand.len_squared() > 1.0 {
@@ -109,12 +109,12 @@ impl Vec3<f32> {
     }
 
     // Lambertian diffusion method
-    pub fn random_unit_vector(rng: &mut ThreadRng) -> Self {
+    pub fn random_unit_vector(rng: &mut Rng) -> Self {
         Self::random_unit_sphere(rng).unit_vector()
     }
 
     // Another approach for diffusion
-    pub fn random_in_hemisphere(rng: &mut ThreadRng, normal: Self) -> Self {
+    pub fn random_in_hemisphere(rng: &mut Rng, normal: Self) -> Self {
         let v = Self::random_unit_sphere(rng);
         // If `v` is in the same side as the `normal`, return it
         if v.dot(&normal) > 0.0 {
@@ -124,9 +124,9 @@ impl Vec3<f32> {
         }
     }
 
-    pub fn random_in_unit_disk(rng: &mut ThreadRng) -> Self {
+    pub fn random_in_unit_disk(rng: &mut Rng) -> Self {
         loop {
-            let point = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
+            let point = Vec3::new(rng.rand_on(-1.0, 1.0), rng.rand_on(-1.0, 1.0), 0.0);
             if point.len_squared() >= 1.0 {
                 continue;
             } else {
@@ -136,6 +136,24 @@ impl Vec3<f32> {
     }
 }
 
+/// Parse a vector written as three comma separated components, such as
+/// `1.0,2.0,3.0`.
+impl<T: FromStr> FromStr for Vec3<T> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let components = s
+            .split(',')
+            .map(|c| c.trim().parse::<T>())
+            .collect::<Result<Vec<T>, _>>()
+            .map_err(|_| format!("invalid vector component in `{}`", s))?;
+        match <[T; 3]>::try_from(components) {
+            Ok([x, y, z]) => Ok(Self { x, y, z }),
+            Err(_) => Err(format!("expected three components `x,y,z`, found `{}`", s)),
+        }
+    }
+}
+
 /// Iterator structure for `Vec3`
 pub struct Vec3IntoIter<T> {
     vec3: Vec3<T>,
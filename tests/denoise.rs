@@ -0,0 +1,117 @@
+//! Peak signal-to-noise ratio of denoised images against references, both on a
+//! synthetic image with known noise and on renders of the `cornell` scene.
+
+use radiant::{
+    color::Color,
+    denoise::Denoiser,
+    misc::Rng,
+    progress::{CancellationToken, SilentProgress},
+    rendering,
+    scene::{self, SceneType},
+    settings::RenderSettings,
+    vec3::Vec3,
+};
+
+/// PSNR of `image` against `reference` in decibels, with the channels clamped
+/// to the displayable range.
+fn psnr(image: &[[f32; 3]], reference: &[[f32; 3]]) -> f32 {
+    assert_eq!(image.len(), reference.len());
+    let squared_error: f32 = image
+        .iter()
+        .flatten()
+        .zip(reference.iter().flatten())
+        .map(|(a, b)| (a.clamp(0.0, 1.0) - b.clamp(0.0, 1.0)).powi(2))
+        .sum();
+    let mse = squared_error / (3 * image.len()) as f32;
+    -10.0 * mse.log10()
+}
+
+/// Linear pixels of the `cornell` scene rendered at `spp` samples per pixel.
+fn render_cornell(spp: u32, denoise: bool) -> Vec<[f32; 3]> {
+    let settings = RenderSettings {
+        image_width: 48,
+        image_height: 48,
+        samples_per_pixel: spp,
+        denoise,
+        ..RenderSettings::default()
+    };
+    let scene = scene::make_scene(SceneType::Cornell, &mut Rng::new(settings.seed as u64));
+    let film = rendering::render_film(
+        scene,
+        &settings,
+        false,
+        &SilentProgress,
+        &CancellationToken::new(),
+    )
+    .unwrap();
+    film.to_rgb32f_image().pixels().map(|p| p.0).collect()
+}
+
+#[test]
+fn denoising_a_render_brings_it_closer_to_the_reference() {
+    let reference = render_cornell(256, false);
+    let noisy = render_cornell(8, false);
+    let denoised = render_cornell(8, true);
+
+    let noisy_psnr = psnr(&noisy, &reference);
+    let denoised_psnr = psnr(&denoised, &reference);
+    assert!(
+        denoised_psnr > noisy_psnr + 4.0,
+        "PSNR went from {:.2} dB to {:.2} dB",
+        noisy_psnr,
+        denoised_psnr
+    );
+}
+
+#[test]
+fn denoising_keeps_edges_between_materials() {
+    // Two flat halves of different albedo under the same light, with uniform
+    // noise of known variance added to every pixel
+    let (width, height) = (64, 64);
+    let albedo_at = |x: usize| {
+        if x < width / 2 {
+            Color::new(0.8, 0.2, 0.2)
+        } else {
+            Color::new(0.2, 0.2, 0.8)
+        }
+    };
+    let irradiance = 0.5;
+    let amplitude = 0.3;
+    let mut rng = Rng::new(7);
+    let mut reference = Vec::new();
+    let mut noisy = Vec::new();
+    let mut variance = Vec::new();
+    let mut albedo = Vec::new();
+    for i in 0..width * height {
+        let a = albedo_at(i % width);
+        reference.push((a * irradiance).channels());
+        noisy.push(a * (irradiance + amplitude * (2.0 * rng.rand() - 1.0)));
+        // Variance of a uniform distribution over [-1, 1] is 1/3
+        variance.push((a.luminance() * amplitude).powi(2) / 3.0);
+        albedo.push(a);
+    }
+    let normal = vec![Vec3::new(0.0, 0.0, 1.0); width * height];
+
+    let denoised: Vec<[f32; 3]> = Denoiser::default()
+        .denoise((width, height), &noisy, &variance, &albedo, &normal)
+        .into_iter()
+        .map(Color::channels)
+        .collect();
+    let noisy: Vec<[f32; 3]> = noisy.into_iter().map(Color::channels).collect();
+
+    let noisy_psnr = psnr(&noisy, &reference);
+    let denoised_psnr = psnr(&denoised, &reference);
+    assert!(
+        denoised_psnr > noisy_psnr + 10.0,
+        "PSNR went from {:.2} dB to {:.2} dB",
+        noisy_psnr,
+        denoised_psnr
+    );
+    // The columns along the edge keep their own albedo
+    for y in 0..height {
+        for x in [width / 2 - 1, width / 2] {
+            let [r, _, b] = denoised[x + y * width];
+            assert_eq!(r > b, x < width / 2, "edge blurred at ({}, {})", x, y);
+        }
+    }
+}
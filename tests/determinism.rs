@@ -0,0 +1,51 @@
+//! Renders with a given seed do not depend on the number of threads.
+
+use radiant::{
+    misc::Rng,
+    progress::{CancellationToken, SilentProgress},
+    rendering,
+    scene::{self, SceneType},
+    settings::RenderSettings,
+};
+use rayon::ThreadPoolBuilder;
+
+/// Bits of the linear pixels and sample counts of the `cornell` scene,
+/// rendered adaptively and denoised with `threads` threads.
+fn render_with_threads(threads: usize) -> Vec<([u32; 3], u32)> {
+    let settings = RenderSettings {
+        image_width: 32,
+        image_height: 24,
+        samples_per_pixel: 8,
+        noise_threshold: Some(0.05),
+        min_spp: 4,
+        max_spp: 32,
+        denoise: true,
+        seed: 5,
+        ..RenderSettings::default()
+    };
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    let film = pool.install(|| {
+        let scene = scene::make_scene(SceneType::Cornell, &mut Rng::new(settings.seed as u64));
+        rendering::render_film(
+            scene,
+            &settings,
+            false,
+            &SilentProgress,
+            &CancellationToken::new(),
+        )
+        .unwrap()
+    });
+    film.to_rgb32f_image()
+        .pixels()
+        .zip(film.pixels())
+        .map(|(p, px)| (p.0.map(f32::to_bits), px.samples()))
+        .collect()
+}
+
+#[test]
+fn renders_are_identical_whatever_the_thread_count() {
+    assert_eq!(render_with_threads(1), render_with_threads(4));
+}
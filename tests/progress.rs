@@ -0,0 +1,38 @@
+//! Cancelling a render from its progress callback.
+
+use radiant::{
+    misc::Rng,
+    progress::{CallbackProgress, CancellationToken},
+    rendering,
+    scene::{self, SceneType},
+    settings::RenderSettings,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[test]
+fn cancelling_returns_the_partial_film() {
+    let settings = RenderSettings {
+        image_width: 32,
+        image_height: 64,
+        samples_per_pixel: 4,
+        denoise: true,
+        ..RenderSettings::default()
+    };
+    let scene = scene::make_scene(SceneType::Cornell, &mut Rng::new(settings.seed as u64));
+    let cancel = CancellationToken::new();
+    let finished = AtomicBool::new(false);
+    let progress = CallbackProgress::new(|progress| {
+        if progress.rows_done >= 4 {
+            cancel.cancel();
+        }
+    })
+    .on_finish(|| finished.store(true, Ordering::Relaxed));
+
+    let film = rendering::render_film(scene, &settings, false, &progress, &cancel).unwrap();
+
+    assert!(cancel.is_cancelled());
+    assert!(finished.load(Ordering::Relaxed));
+    let unsampled = film.pixels().iter().filter(|px| px.samples() == 0).count();
+    assert!(unsampled > 0, "every pixel was sampled");
+    assert!(unsampled < film.pixels().len(), "no pixel was sampled");
+}